@@ -0,0 +1,78 @@
+// The system API used by the canister.  Native tests run without a replica, so
+// they simulate it: the controller calls, unless a test sets another caller, at
+// a fixed time, the randomness counts up from [1; 32] and the certified data is
+// kept for the test to sign.
+
+#[cfg(not(test))]
+pub use ic_cdk::api::{caller, data_certificate, id, is_controller, set_certified_data, time};
+
+// 32 random bytes from the management canister.
+#[cfg(not(test))]
+pub async fn raw_rand() -> Result<Vec<u8>, String> {
+    let management = candid::Principal::management_canister();
+    match ic_cdk::call(management, "raw_rand", ()).await {
+        Ok((rand,)) => Ok(rand),
+        Err((_, err)) => Err(err),
+    }
+}
+
+#[cfg(test)]
+pub use native::*;
+
+#[cfg(test)]
+mod native {
+    use candid::Principal;
+    use std::cell::RefCell;
+
+    // The only controller and the caller.
+    pub const CONTROLLER: Principal = Principal::from_slice(&[1]);
+    pub const CANISTER_ID: Principal = Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 1, 1]);
+
+    thread_local! {
+        static CALLER: RefCell<Principal> = const { RefCell::new(CONTROLLER) };
+        static TIME: RefCell<u64> = const { RefCell::new(1_700_000_000_000_000_000) };
+        static RAND: RefCell<u8> = const { RefCell::new(0) };
+        static CERTIFIED_DATA: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    }
+
+    pub fn caller() -> Principal {
+        CALLER.with(|c| *c.borrow())
+    }
+
+    pub fn set_caller(principal: Principal) {
+        CALLER.with(|c| *c.borrow_mut() = principal);
+    }
+
+    pub fn is_controller(principal: &Principal) -> bool {
+        *principal == CONTROLLER
+    }
+
+    pub fn id() -> Principal {
+        CANISTER_ID
+    }
+
+    pub fn time() -> u64 {
+        TIME.with(|t| *t.borrow())
+    }
+
+    pub fn set_certified_data(data: &[u8]) {
+        CERTIFIED_DATA.with(|d| *d.borrow_mut() = data.to_vec());
+    }
+
+    pub fn certified_data() -> Vec<u8> {
+        CERTIFIED_DATA.with(|d| d.borrow().clone())
+    }
+
+    // There is no certificate outside of query calls.
+    pub fn data_certificate() -> Option<Vec<u8>> {
+        None
+    }
+
+    pub async fn raw_rand() -> Result<Vec<u8>, String> {
+        let rand = RAND.with(|r| {
+            *r.borrow_mut() += 1;
+            *r.borrow()
+        });
+        Ok(vec![rand; 32])
+    }
+}
@@ -0,0 +1,106 @@
+// Block types shared with the offline verifier.
+
+use candid::{CandidType, Deserialize, Principal};
+
+type Blob = Vec<u8>;
+type Hash = [u8; 32];
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize, FromPrimitive)]
+pub enum Kind {
+    #[default]
+    Add,
+    Remove,
+    Select,
+    Expand,
+    Authorize,
+    Deauthorize,
+    Close,
+    Replace,
+    Strike,
+    Promote,
+}
+
+// Roles of authorized principals, recorded in Authorize blocks.
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, FromPrimitive, PartialEq)]
+pub enum Role {
+    // add, remove and set_cooldown.
+    PoolManager,
+    // select, expand, replace, strike, promote_alternate and close.
+    Selector,
+    // commit.
+    Committer,
+    // authorize and deauthorize.
+    AuthAdmin,
+}
+
+// The number of jurors of a category in a stratified selection.
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub struct Quota {
+    pub category: String,
+    pub count: u32,
+}
+
+// How long after a selection its jurors are not drawn again from the same pool.
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, PartialEq)]
+pub enum Cooldown {
+    // Log blocks after the block which drew the juror.
+    Blocks(u32),
+    // Nanoseconds after the block which drew the juror was staged.
+    Nanoseconds(u64),
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct Data {
+    pub kind: Kind,
+    pub jurors: Vec<Blob>,
+    pub rand: Option<Blob>,
+    pub memo: Blob,
+    pub algorithm: Option<u32>,
+    pub pool_index: Option<u32>,
+    pub jurors_index: Option<u32>,
+    pub principal: Option<Principal>,
+    pub roles: Option<Vec<Role>>,
+    // The pool of Add, Remove, Select, Expand and Replace blocks, None for the
+    // default pool.
+    pub pool: Option<String>,
+    // The per-jury blinded ids of the jurors and alternates of Select, Expand and
+    // Replace blocks who were added with a commitment, sorted, see
+    // selection::blind().
+    pub blinded: Option<Vec<Blob>>,
+    // The commitments sha256(secret) of the jurors of Add blocks, in the same
+    // order, None for jurors without one.
+    pub commitments: Option<Vec<Option<Blob>>>,
+    // The selection weights of the jurors of Add blocks, in the same order.
+    // Jurors added without one have weight 1.
+    pub weights: Option<Vec<u32>>,
+    // The categories of the jurors of Add blocks, in the same order, "" for none.
+    pub categories: Option<Vec<String>>,
+    // The quotas of stratified Select blocks, drawn in this order.
+    pub quotas: Option<Vec<Quota>>,
+    // The jurors excluded from the pool by Select blocks and the Expand and
+    // Replace blocks extending them.
+    pub exclude: Option<Vec<Blob>>,
+    // The cooldown of the pool when selected, copied by the Expand and Replace
+    // blocks extending the selection.
+    pub cooldown: Option<Cooldown>,
+    // The staging time of Select, Expand and Replace blocks in nanoseconds.
+    pub time: Option<u64>,
+    // The jurors of the open juries of the pool excluded by Select blocks when
+    // selected, copied by the Expand and Replace blocks extending them.
+    pub serving: Option<Vec<Blob>>,
+    // The jurors replaced by the jurors of Replace blocks, in the same order.
+    pub jurors_out: Option<Vec<Blob>>,
+    // The number of jurors each party may strike from the jury of Select blocks.
+    pub strikes: Option<u32>,
+    // The alternates drawn by Select blocks after the jurors, seated in this
+    // order by Promote blocks.
+    pub alternates: Option<Vec<Blob>>,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct Block {
+    pub certificate: Blob,
+    pub tree: Blob,
+    pub data: Data,
+    pub previous_hash: Hash,
+}
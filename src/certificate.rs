@@ -0,0 +1,321 @@
+// Verification of IC certificates, shared with the offline verifier, cf.
+// https://internetcomputer.org/docs/current/references/ic-interface-spec#certification
+
+use crate::hash_tree::{HashTree, Label, LookupResult};
+use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+use bls12_381::{multi_miller_loop, G1Affine, G1Projective, G2Affine, G2Prepared, Gt};
+use serde::Deserialize;
+
+const BLS_SIGNATURE_DOMAIN_SEP: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_";
+const DER_PREFIX: &[u8; 37] = b"\x30\x81\x82\x30\x1d\x06\x0d\x2b\x06\x01\x04\x01\x82\xdc\x7c\x05\x03\x01\x02\x01\x06\x0c\x2b\x06\x01\x04\x01\x82\xdc\x7c\x05\x03\x02\x01\x03\x61\x00";
+const KEY_LENGTH: usize = 96;
+// The IC mainnet root key (DER).
+pub const IC_ROOT_KEY: &str = "308182301d060d2b0601040182dc7c0503010201060c2b0601040182dc7c05030201036100814c0e6ec71fab583b08bd81373c255c3c371b2e84863c98a4f1e08b74235d14fb5d9c0cd546d9685f913a0c0b2cc5341583bf4b4392e467db96d65b9bb4cb717112f8472e0d5a4d14505ffd7484b01291091c5f87b98883463f98091a0baaae";
+
+#[derive(Deserialize)]
+pub struct Certificate {
+    pub tree: HashTree<'static>,
+    signature: serde_bytes::ByteBuf,
+    delegation: Option<Delegation>,
+}
+
+#[derive(Deserialize)]
+struct Delegation {
+    subnet_id: serde_bytes::ByteBuf,
+    certificate: serde_bytes::ByteBuf,
+}
+
+impl Certificate {
+    pub fn from_cbor(bytes: &[u8]) -> Result<Certificate, String> {
+        serde_cbor::from_slice(bytes).map_err(|e| format!("malformed certificate: {}", e))
+    }
+
+    // Verify the signature against the root key (DER or raw) and, if present,
+    // the delegation of the root key to the subnet for the canister.
+    pub fn verify(&self, root_key: &[u8], canister_id: &[u8]) -> Result<(), String> {
+        let key = match &self.delegation {
+            None => root_key.to_vec(),
+            Some(delegation) => delegation.verify(root_key, canister_id)?,
+        };
+        self.verify_signature(&key)
+    }
+
+    // The certified data of the canister.
+    pub fn certified_data(&self, canister_id: &[u8]) -> Option<Vec<u8>> {
+        lookup(
+            &self.tree,
+            &[
+                "canister".into(),
+                canister_id.into(),
+                "certified_data".into(),
+            ],
+        )
+    }
+
+    // The time of the certificate in nanoseconds since the epoch.
+    pub fn time(&self) -> Option<u64> {
+        let bytes = lookup(&self.tree, &["time".into()])?;
+        let mut time: u64 = 0;
+        for (i, b) in bytes.iter().enumerate() {
+            time |= ((b & 0x7f) as u64).checked_shl(7 * i as u32)?;
+            if b & 0x80 == 0 {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    fn verify_signature(&self, der_key: &[u8]) -> Result<(), String> {
+        let key = extract_der(der_key)?;
+        let mut msg = vec![13];
+        msg.extend_from_slice(b"ic-state-root");
+        msg.extend_from_slice(&self.tree.digest());
+        if verify_bls_signature(&self.signature, &msg, &key) {
+            Ok(())
+        } else {
+            Err("certificate signature verification failed".to_string())
+        }
+    }
+}
+
+impl Delegation {
+    // Verify the delegation and return the subnet public key.
+    fn verify(&self, root_key: &[u8], canister_id: &[u8]) -> Result<Vec<u8>, String> {
+        let certificate = Certificate::from_cbor(&self.certificate)?;
+        if certificate.delegation.is_some() {
+            return Err("certificate has multiple delegations".to_string());
+        }
+        certificate.verify_signature(root_key)?;
+        let subnet_id: &[u8] = &self.subnet_id;
+        let ranges = lookup(
+            &certificate.tree,
+            &["subnet".into(), subnet_id.into(), "canister_ranges".into()],
+        )
+        .ok_or("delegation has no canister ranges")?;
+        let ranges: Vec<(serde_bytes::ByteBuf, serde_bytes::ByteBuf)> =
+            serde_cbor::from_slice(&ranges).map_err(|e| format!("malformed ranges: {}", e))?;
+        if !ranges
+            .iter()
+            .any(|(low, high)| low.as_slice() <= canister_id && canister_id <= high.as_slice())
+        {
+            return Err("canister is not in the delegated ranges".to_string());
+        }
+        lookup(
+            &certificate.tree,
+            &["subnet".into(), subnet_id.into(), "public_key".into()],
+        )
+        .ok_or_else(|| "delegation has no public key".to_string())
+    }
+}
+
+fn lookup(tree: &HashTree, path: &[Label]) -> Option<Vec<u8>> {
+    match tree.lookup_path(path) {
+        LookupResult::Found(value) => Some(value.to_vec()),
+        _ => None,
+    }
+}
+
+fn extract_der(key: &[u8]) -> Result<Vec<u8>, String> {
+    if key.len() == KEY_LENGTH {
+        return Ok(key.to_vec());
+    }
+    if key.len() != DER_PREFIX.len() + KEY_LENGTH || !key.starts_with(DER_PREFIX) {
+        return Err("malformed public key".to_string());
+    }
+    Ok(key[DER_PREFIX.len()..].to_vec())
+}
+
+// BLS12-381 signature in G1 (48 bytes) with public key in G2 (96 bytes).
+pub fn verify_bls_signature(signature: &[u8], msg: &[u8], key: &[u8]) -> bool {
+    let (Ok(signature), Ok(key)) = (<&[u8; 48]>::try_from(signature), <&[u8; 96]>::try_from(key))
+    else {
+        return false;
+    };
+    let signature: Option<G1Affine> = G1Affine::from_compressed(signature).into();
+    let key: Option<G2Affine> = G2Affine::from_compressed(key).into();
+    let (Some(signature), Some(key)) = (signature, key) else {
+        return false;
+    };
+    let msg = G1Affine::from(<G1Projective as HashToCurve<
+        ExpandMsgXmd<sha2_09::Sha256>,
+    >>::hash_to_curve(msg, BLS_SIGNATURE_DOMAIN_SEP));
+    // e(signature, g2) == e(msg, key)
+    let g2 = G2Prepared::from(-G2Affine::generator());
+    let key = G2Prepared::from(key);
+    multi_miller_loop(&[(&signature, &g2), (&msg, &key)]).final_exponentiation() == Gt::identity()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::hash_tree::{fork, label, leaf};
+    use bls12_381::{G2Projective, Scalar};
+    use serde::Serialize;
+
+    const CANISTER_ID: &[u8] = &[0, 0, 0, 0, 0, 0, 0, 1, 1, 1];
+    const SUBNET_ID: &[u8] = &[1, 2, 3];
+
+    #[derive(Serialize)]
+    struct TestCertificate<'a> {
+        tree: HashTree<'a>,
+        signature: serde_bytes::ByteBuf,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        delegation: Option<TestDelegation>,
+    }
+
+    #[derive(Serialize)]
+    pub(crate) struct TestDelegation {
+        subnet_id: serde_bytes::ByteBuf,
+        certificate: serde_bytes::ByteBuf,
+    }
+
+    pub(crate) fn public_key(secret: u64) -> Vec<u8> {
+        let key = G2Affine::from(G2Projective::generator() * Scalar::from(secret));
+        key.to_compressed().to_vec()
+    }
+
+    fn sign(secret: u64, msg: &[u8]) -> Vec<u8> {
+        let msg = <G1Projective as HashToCurve<ExpandMsgXmd<sha2_09::Sha256>>>::hash_to_curve(
+            msg,
+            BLS_SIGNATURE_DOMAIN_SEP,
+        );
+        G1Affine::from(msg * Scalar::from(secret))
+            .to_compressed()
+            .to_vec()
+    }
+
+    // A certificate of tree signed with secret, CBOR encoded.
+    pub(crate) fn certificate(
+        tree: HashTree,
+        secret: u64,
+        delegation: Option<TestDelegation>,
+    ) -> Vec<u8> {
+        let mut msg = vec![13];
+        msg.extend_from_slice(b"ic-state-root");
+        msg.extend_from_slice(&tree.digest());
+        let certificate = TestCertificate {
+            signature: serde_bytes::ByteBuf::from(sign(secret, &msg)),
+            tree,
+            delegation,
+        };
+        serde_cbor::to_vec(&certificate).unwrap()
+    }
+
+    fn canister_tree(certified_data: &[u8]) -> HashTree<'static> {
+        fork(
+            label(
+                "canister",
+                label(CANISTER_ID, label("certified_data", leaf(certified_data))),
+            ),
+            label("time", leaf(vec![0x80, 0x01])),
+        )
+    }
+
+    // A delegation from the root key with secret to the subnet key with
+    // subnet_secret for the canisters in low..=high.
+    pub(crate) fn delegation(
+        secret: u64,
+        subnet_secret: u64,
+        low: &[u8],
+        high: &[u8],
+    ) -> TestDelegation {
+        let ranges = serde_cbor::to_vec(&vec![(
+            serde_bytes::ByteBuf::from(low.to_vec()),
+            serde_bytes::ByteBuf::from(high.to_vec()),
+        )])
+        .unwrap();
+        let tree = label(
+            "subnet",
+            label(
+                SUBNET_ID,
+                fork(
+                    label("canister_ranges", leaf(ranges)),
+                    label("public_key", leaf(public_key(subnet_secret))),
+                ),
+            ),
+        );
+        TestDelegation {
+            subnet_id: serde_bytes::ByteBuf::from(SUBNET_ID.to_vec()),
+            certificate: serde_bytes::ByteBuf::from(certificate(tree, secret, None)),
+        }
+    }
+
+    #[test]
+    fn verify_root_signature() {
+        let bytes = certificate(canister_tree(b"data"), 7, None);
+        let certificate = Certificate::from_cbor(&bytes).unwrap();
+        certificate.verify(&public_key(7), CANISTER_ID).unwrap();
+        let mut der = DER_PREFIX.to_vec();
+        der.extend(public_key(7));
+        certificate.verify(&der, CANISTER_ID).unwrap();
+        assert!(certificate.verify(&public_key(8), CANISTER_ID).is_err());
+        assert_eq!(
+            certificate.certified_data(CANISTER_ID),
+            Some(b"data".to_vec())
+        );
+        assert_eq!(certificate.certified_data(&[1]), None);
+        assert_eq!(certificate.time(), Some(128));
+    }
+
+    #[test]
+    fn verify_tampered_tree() {
+        let bytes = certificate(canister_tree(b"data"), 7, None);
+        let mut certificate = Certificate::from_cbor(&bytes).unwrap();
+        certificate.tree = canister_tree(b"other");
+        assert!(certificate.verify(&public_key(7), CANISTER_ID).is_err());
+    }
+
+    #[test]
+    fn verify_delegation() {
+        let ranges = (&[0][..], &[0, 0, 0, 0, 0, 0, 0, 2][..]);
+        let bytes = certificate(
+            canister_tree(b"data"),
+            9,
+            Some(delegation(7, 9, ranges.0, ranges.1)),
+        );
+        let cert = Certificate::from_cbor(&bytes).unwrap();
+        cert.verify(&public_key(7), CANISTER_ID).unwrap();
+        // The delegation must be signed by the root key.
+        assert!(cert.verify(&public_key(9), CANISTER_ID).is_err());
+        // The certificate must be signed by the subnet key.
+        let bytes = certificate(
+            canister_tree(b"data"),
+            8,
+            Some(delegation(7, 9, ranges.0, ranges.1)),
+        );
+        let cert = Certificate::from_cbor(&bytes).unwrap();
+        assert!(cert.verify(&public_key(7), CANISTER_ID).is_err());
+    }
+
+    #[test]
+    fn verify_delegation_range() {
+        let delegation = delegation(7, 9, &[0, 0, 0, 0, 0, 0, 0, 2], &[0, 0, 0, 0, 0, 0, 0, 3]);
+        let bytes = certificate(canister_tree(b"data"), 9, Some(delegation));
+        let certificate = Certificate::from_cbor(&bytes).unwrap();
+        let error = certificate.verify(&public_key(7), CANISTER_ID).unwrap_err();
+        assert_eq!(error, "canister is not in the delegated ranges");
+    }
+
+    #[test]
+    fn verify_nested_delegation() {
+        let inner = delegation(7, 9, &[0], &[0, 0, 0, 0, 0, 0, 0, 2]);
+        let tree = label(
+            "subnet",
+            label(SUBNET_ID, label("public_key", leaf(public_key(11)))),
+        );
+        let outer = TestDelegation {
+            subnet_id: serde_bytes::ByteBuf::from(SUBNET_ID.to_vec()),
+            certificate: serde_bytes::ByteBuf::from(certificate(tree, 9, Some(inner))),
+        };
+        let bytes = certificate(canister_tree(b"data"), 11, Some(outer));
+        let certificate = Certificate::from_cbor(&bytes).unwrap();
+        let error = certificate.verify(&public_key(7), CANISTER_ID).unwrap_err();
+        assert_eq!(error, "certificate has multiple delegations");
+    }
+
+    #[test]
+    fn malformed_key() {
+        assert!(extract_der(&[0; 95]).is_err());
+        assert!(!verify_bls_signature(&[0; 48], b"msg", &public_key(7)));
+    }
+}
@@ -1,51 +1,177 @@
 import type { Principal } from '@dfinity/principal';
 import type { ActorMethod } from '@dfinity/agent';
 
+export interface Authorization { 'id' : Principal, 'roles' : Array<Role> }
 export interface Block {
   'certificate' : Uint8Array | number[],
   'data' : Data,
   'tree' : Uint8Array | number[],
   'previous_hash' : Uint8Array | number[],
 }
+export interface Blocks { 'next' : [] | [number], 'blocks' : Array<Block> }
+export type Cooldown = { 'Blocks' : number } |
+  { 'Nanoseconds' : bigint };
 export interface Data {
+  'categories' : [] | [Array<string>],
+  'serving' : [] | [Array<Uint8Array | number[]>],
   'jurors' : Array<Uint8Array | number[]>,
+  'principal' : [] | [Principal],
+  'algorithm' : [] | [number],
+  'jurors_index' : [] | [number],
   'kind' : Kind,
   'memo' : Uint8Array | number[],
+  'pool' : [] | [string],
   'rand' : [] | [Uint8Array | number[]],
+  'time' : [] | [bigint],
+  'alternates' : [] | [Array<Uint8Array | number[]>],
+  'weights' : [] | [Uint32Array | number[]],
+  'jurors_out' : [] | [Array<Uint8Array | number[]>],
+  'pool_index' : [] | [number],
+  'exclude' : [] | [Array<Uint8Array | number[]>],
+  'quotas' : [] | [Array<Quota>],
+  'commitments' : [] | [Array<[] | [Uint8Array | number[]]>],
+  'blinded' : [] | [Array<Uint8Array | number[]>],
+  'roles' : [] | [Array<Role>],
+  'strikes' : [] | [number],
+  'cooldown' : [] | [Cooldown],
 }
+export type JuryError = { 'PoolTooSmall' : { 'count' : number, 'size' : number } } |
+  { 'NotOnPanel' : Uint8Array | number[] } |
+  { 'StrikeLimit' : { 'limit' : number, 'party' : Principal } } |
+  { 'NotASelection' : number } |
+  { 'CategoryTooLarge' : string } |
+  { 'InvalidCertificate' : string } |
+  { 'NoAlternates' : number } |
+  { 'UnknownPrincipal' : Principal } |
+  { 'CertificateMismatch' : null } |
+  { 'WeightCount' : { 'jurors' : number, 'weights' : number } } |
+  { 'SecretCount' : { 'jurors' : number, 'secrets' : number } } |
+  { 'JuryClosed' : number } |
+  { 'PoolIdTooLarge' : string } |
+  { 'StratifiedAlternates' : null } |
+  { 'MalformedCertificate' : string } |
+  { 'NotExpandable' : number } |
+  { 'IndexOutOfRange' : { 'length' : number, 'index' : number } } |
+  { 'CertificateTooOld' : bigint } |
+  { 'Unauthorized' : null } |
+  { 'InvalidSecret' : number } |
+  { 'NoJurors' : null } |
+  { 'Randomness' : string } |
+  { 'StratumTooSmall' : { 'count' : number, 'size' : number, 'category' : string } } |
+  { 'CategoryCount' : { 'categories' : number, 'jurors' : number } } |
+  { 'DuplicateCategory' : string };
 export type Kind = { 'Add' : null } |
   { 'Remove' : null } |
+  { 'Strike' : null } |
+  { 'Authorize' : null } |
+  { 'Replace' : null } |
   { 'Select' : null } |
+  { 'Promote' : null } |
+  { 'Deauthorize' : null } |
+  { 'Close' : null } |
   { 'Expand' : null };
+export interface Quota { 'count' : number, 'category' : string }
+export type Result = { 'Ok' : number } |
+  { 'Err' : JuryError };
+export type Result_1 = { 'Ok' : boolean } |
+  { 'Err' : JuryError };
+export type Result_2 = { 'Ok' : [] | [number] } |
+  { 'Err' : JuryError };
+export type Result_3 = { 'Ok' : Array<[] | [number]> } |
+  { 'Err' : JuryError };
+export type Result_4 = { 'Ok' : Block } |
+  { 'Err' : JuryError };
+export type Result_5 = { 'Ok' : [] | [Cooldown] } |
+  { 'Err' : JuryError };
+export type Result_6 = { 'Ok' : Array<Uint8Array | number[]> } |
+  { 'Err' : JuryError };
+export type Result_7 = { 'Ok' : null } |
+  { 'Err' : JuryError };
+export type Role = { 'Committer' : null } |
+  { 'PoolManager' : null } |
+  { 'Selector' : null } |
+  { 'AuthAdmin' : null };
+export interface SelectOptions {
+  'exclude_serving' : [] | [boolean],
+  'pool' : [] | [string],
+  'alternates' : [] | [number],
+  'exclude' : [] | [Array<Uint8Array | number[]>],
+  'strikes' : [] | [number],
+}
 export interface _SERVICE {
   'add' : ActorMethod<
-    [Array<Uint8Array | number[]>, Uint8Array | number[]],
-    number
+    [
+      Array<Uint8Array | number[]>,
+      Uint8Array | number[],
+      [] | [string],
+      [] | [Array<[] | [Uint8Array | number[]]>],
+      [] | [Uint32Array | number[]],
+      [] | [Array<string>],
+    ],
+    Result
   >,
-  'authorize' : ActorMethod<[Principal], undefined>,
-  'commit' : ActorMethod<[Uint8Array | number[]], [] | [number]>,
-  'deauthorize' : ActorMethod<[Principal], undefined>,
-  'expand' : ActorMethod<[number, number, Uint8Array | number[]], number>,
+  'authorize' : ActorMethod<[Principal, [] | [Array<Role>]], Result>,
+  'check_token' : ActorMethod<[number, Uint8Array | number[]], Result_1>,
+  'close' : ActorMethod<[number, Uint8Array | number[]], Result>,
+  'commit' : ActorMethod<[Uint8Array | number[]], Result_2>,
+  'deauthorize' : ActorMethod<[Principal], Result>,
+  'expand' : ActorMethod<[number, number, Uint8Array | number[]], Result>,
   'find' : ActorMethod<
-    [number, Array<Uint8Array | number[]>],
-    Array<[] | [number]>
+    [number, Array<Uint8Array | number[]>, [] | [string]],
+    Result_3
   >,
-  'get_authorized' : ActorMethod<[], Array<Principal>>,
-  'get_block' : ActorMethod<[number], Block>,
+  'get_authorized' : ActorMethod<[], Array<Authorization>>,
+  'get_block' : ActorMethod<[number], Result_4>,
+  'get_blocks' : ActorMethod<[number, number], Blocks>,
   'get_certificate' : ActorMethod<[], [] | [Uint8Array | number[]]>,
-  'get_history' : ActorMethod<[Uint8Array | number[]], Uint32Array | number[]>,
-  'get_jurors' : ActorMethod<[number], Array<Uint8Array | number[]>>,
+  'get_cooldown' : ActorMethod<[[] | [string]], Result_5>,
+  'get_history' : ActorMethod<
+    [Uint8Array | number[], [] | [string]],
+    Uint32Array | number[]
+  >,
+  'get_jurors' : ActorMethod<[number], Result_6>,
+  'get_panel' : ActorMethod<[number], Result_6>,
   'get_pending' : ActorMethod<[], number>,
   'get_pool' : ActorMethod<
-    [number, number, number],
-    Array<Uint8Array | number[]>
+    [number, number, number, [] | [string]],
+    Result_6
   >,
-  'get_pool_size' : ActorMethod<[number], number>,
-  'get_size' : ActorMethod<[number], number>,
+  'get_pool_size' : ActorMethod<[number, [] | [string]], Result>,
+  'get_pools' : ActorMethod<[], Array<string>>,
+  'get_size' : ActorMethod<[number], Result>,
   'length' : ActorMethod<[], number>,
+  'promote_alternate' : ActorMethod<
+    [number, [] | [Uint8Array | number[]], Uint8Array | number[]],
+    Result
+  >,
   'remove' : ActorMethod<
-    [Array<Uint8Array | number[]>, Uint8Array | number[]],
-    number
+    [Array<Uint8Array | number[]>, Uint8Array | number[], [] | [string]],
+    Result
+  >,
+  'replace' : ActorMethod<
+    [number, Array<Uint8Array | number[]>, Uint8Array | number[]],
+    Result
+  >,
+  'select' : ActorMethod<
+    [number, number, Uint8Array | number[], [] | [SelectOptions]],
+    Result
+  >,
+  'select_stratified' : ActorMethod<
+    [number, Array<Quota>, Uint8Array | number[], [] | [SelectOptions]],
+    Result
+  >,
+  'select_weighted' : ActorMethod<
+    [number, number, Uint8Array | number[], [] | [SelectOptions]],
+    Result
+  >,
+  'set_cooldown' : ActorMethod<[[] | [Cooldown], [] | [string]], Result_7>,
+  'strike' : ActorMethod<
+    [
+      number,
+      Array<Uint8Array | number[]>,
+      Principal,
+      Uint8Array | number[],
+    ],
+    Result
   >,
-  'select' : ActorMethod<[number, number, Uint8Array | number[]], number>,
 }
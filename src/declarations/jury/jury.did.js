@@ -2,14 +2,49 @@ export const idlFactory = ({ IDL }) => {
   const Kind = IDL.Variant({
     'Add' : IDL.Null,
     'Remove' : IDL.Null,
+    'Strike' : IDL.Null,
+    'Authorize' : IDL.Null,
+    'Replace' : IDL.Null,
     'Select' : IDL.Null,
+    'Promote' : IDL.Null,
+    'Deauthorize' : IDL.Null,
+    'Close' : IDL.Null,
     'Expand' : IDL.Null,
   });
+  const Role = IDL.Variant({
+    'Committer' : IDL.Null,
+    'PoolManager' : IDL.Null,
+    'Selector' : IDL.Null,
+    'AuthAdmin' : IDL.Null,
+  });
+  const Quota = IDL.Record({ 'count' : IDL.Nat32, 'category' : IDL.Text });
+  const Cooldown = IDL.Variant({
+    'Blocks' : IDL.Nat32,
+    'Nanoseconds' : IDL.Nat64,
+  });
   const Data = IDL.Record({
+    'categories' : IDL.Opt(IDL.Vec(IDL.Text)),
+    'serving' : IDL.Opt(IDL.Vec(IDL.Vec(IDL.Nat8))),
     'jurors' : IDL.Vec(IDL.Vec(IDL.Nat8)),
+    'principal' : IDL.Opt(IDL.Principal),
+    'algorithm' : IDL.Opt(IDL.Nat32),
+    'jurors_index' : IDL.Opt(IDL.Nat32),
     'kind' : Kind,
     'memo' : IDL.Vec(IDL.Nat8),
+    'pool' : IDL.Opt(IDL.Text),
     'rand' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'time' : IDL.Opt(IDL.Nat64),
+    'alternates' : IDL.Opt(IDL.Vec(IDL.Vec(IDL.Nat8))),
+    'weights' : IDL.Opt(IDL.Vec(IDL.Nat32)),
+    'jurors_out' : IDL.Opt(IDL.Vec(IDL.Vec(IDL.Nat8))),
+    'pool_index' : IDL.Opt(IDL.Nat32),
+    'exclude' : IDL.Opt(IDL.Vec(IDL.Vec(IDL.Nat8))),
+    'quotas' : IDL.Opt(IDL.Vec(Quota)),
+    'commitments' : IDL.Opt(IDL.Vec(IDL.Opt(IDL.Vec(IDL.Nat8)))),
+    'blinded' : IDL.Opt(IDL.Vec(IDL.Vec(IDL.Nat8))),
+    'roles' : IDL.Opt(IDL.Vec(Role)),
+    'strikes' : IDL.Opt(IDL.Nat32),
+    'cooldown' : IDL.Opt(Cooldown),
   });
   const Block = IDL.Record({
     'certificate' : IDL.Vec(IDL.Nat8),
@@ -17,57 +52,180 @@ export const idlFactory = ({ IDL }) => {
     'tree' : IDL.Vec(IDL.Nat8),
     'previous_hash' : IDL.Vec(IDL.Nat8),
   });
+  const JuryError = IDL.Variant({
+    'PoolTooSmall' : IDL.Record({ 'count' : IDL.Nat32, 'size' : IDL.Nat32 }),
+    'NotOnPanel' : IDL.Vec(IDL.Nat8),
+    'StrikeLimit' : IDL.Record({ 'limit' : IDL.Nat32, 'party' : IDL.Principal }),
+    'NotASelection' : IDL.Nat32,
+    'CategoryTooLarge' : IDL.Text,
+    'InvalidCertificate' : IDL.Text,
+    'NoAlternates' : IDL.Nat32,
+    'UnknownPrincipal' : IDL.Principal,
+    'CertificateMismatch' : IDL.Null,
+    'WeightCount' : IDL.Record({ 'jurors' : IDL.Nat32, 'weights' : IDL.Nat32 }),
+    'SecretCount' : IDL.Record({ 'jurors' : IDL.Nat32, 'secrets' : IDL.Nat32 }),
+    'JuryClosed' : IDL.Nat32,
+    'PoolIdTooLarge' : IDL.Text,
+    'StratifiedAlternates' : IDL.Null,
+    'MalformedCertificate' : IDL.Text,
+    'NotExpandable' : IDL.Nat32,
+    'IndexOutOfRange' : IDL.Record({ 'length' : IDL.Nat32, 'index' : IDL.Nat32 }),
+    'CertificateTooOld' : IDL.Nat64,
+    'Unauthorized' : IDL.Null,
+    'InvalidSecret' : IDL.Nat32,
+    'NoJurors' : IDL.Null,
+    'Randomness' : IDL.Text,
+    'StratumTooSmall' : IDL.Record({
+      'count' : IDL.Nat32,
+      'size' : IDL.Nat32,
+      'category' : IDL.Text,
+    }),
+    'CategoryCount' : IDL.Record({
+      'categories' : IDL.Nat32,
+      'jurors' : IDL.Nat32,
+    }),
+    'DuplicateCategory' : IDL.Text,
+  });
+  const Result = IDL.Variant({ 'Ok' : IDL.Nat32, 'Err' : JuryError });
+  const Result_1 = IDL.Variant({ 'Ok' : IDL.Bool, 'Err' : JuryError });
+  const Result_2 = IDL.Variant({ 'Ok' : IDL.Opt(IDL.Nat32), 'Err' : JuryError });
+  const Result_3 = IDL.Variant({
+    'Ok' : IDL.Vec(IDL.Opt(IDL.Nat32)),
+    'Err' : JuryError,
+  });
+  const Result_4 = IDL.Variant({ 'Ok' : Block, 'Err' : JuryError });
+  const Result_5 = IDL.Variant({
+    'Ok' : IDL.Opt(Cooldown),
+    'Err' : JuryError,
+  });
+  const Result_6 = IDL.Variant({
+    'Ok' : IDL.Vec(IDL.Vec(IDL.Nat8)),
+    'Err' : JuryError,
+  });
+  const Result_7 = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : JuryError });
+  const Authorization = IDL.Record({ 'id' : IDL.Principal, 'roles' : IDL.Vec(Role) });
+  const Blocks = IDL.Record({
+    'next' : IDL.Opt(IDL.Nat32),
+    'blocks' : IDL.Vec(Block),
+  });
+  const SelectOptions = IDL.Record({
+    'exclude_serving' : IDL.Opt(IDL.Bool),
+    'pool' : IDL.Opt(IDL.Text),
+    'alternates' : IDL.Opt(IDL.Nat32),
+    'exclude' : IDL.Opt(IDL.Vec(IDL.Vec(IDL.Nat8))),
+    'strikes' : IDL.Opt(IDL.Nat32),
+  });
   return IDL.Service({
     'add' : IDL.Func(
-        [IDL.Vec(IDL.Vec(IDL.Nat8)), IDL.Vec(IDL.Nat8)],
-        [IDL.Nat32],
+        [
+          IDL.Vec(IDL.Vec(IDL.Nat8)),
+          IDL.Vec(IDL.Nat8),
+          IDL.Opt(IDL.Text),
+          IDL.Opt(IDL.Vec(IDL.Opt(IDL.Vec(IDL.Nat8)))),
+          IDL.Opt(IDL.Vec(IDL.Nat32)),
+          IDL.Opt(IDL.Vec(IDL.Text)),
+        ],
+        [Result],
+        [],
+      ),
+    'authorize' : IDL.Func(
+        [IDL.Principal, IDL.Opt(IDL.Vec(Role))],
+        [Result],
         [],
       ),
-    'authorize' : IDL.Func([IDL.Principal], [], []),
-    'commit' : IDL.Func([IDL.Vec(IDL.Nat8)], [IDL.Opt(IDL.Nat32)], []),
-    'deauthorize' : IDL.Func([IDL.Principal], [], []),
+    'check_token' : IDL.Func(
+        [IDL.Nat32, IDL.Vec(IDL.Nat8)],
+        [Result_1],
+        ['query'],
+      ),
+    'close' : IDL.Func([IDL.Nat32, IDL.Vec(IDL.Nat8)], [Result], []),
+    'commit' : IDL.Func([IDL.Vec(IDL.Nat8)], [Result_2], []),
+    'deauthorize' : IDL.Func([IDL.Principal], [Result], []),
     'expand' : IDL.Func(
         [IDL.Nat32, IDL.Nat32, IDL.Vec(IDL.Nat8)],
-        [IDL.Nat32],
+        [Result],
         [],
       ),
     'find' : IDL.Func(
-        [IDL.Nat32, IDL.Vec(IDL.Vec(IDL.Nat8))],
-        [IDL.Vec(IDL.Opt(IDL.Nat32))],
+        [IDL.Nat32, IDL.Vec(IDL.Vec(IDL.Nat8)), IDL.Opt(IDL.Text)],
+        [Result_3],
         ['query'],
       ),
-    'get_authorized' : IDL.Func([], [IDL.Vec(IDL.Principal)], ['query']),
-    'get_block' : IDL.Func([IDL.Nat32], [Block], ['query']),
+    'get_authorized' : IDL.Func([], [IDL.Vec(Authorization)], ['query']),
+    'get_block' : IDL.Func([IDL.Nat32], [Result_4], ['query']),
+    'get_blocks' : IDL.Func([IDL.Nat32, IDL.Nat32], [Blocks], ['query']),
     'get_certificate' : IDL.Func([], [IDL.Opt(IDL.Vec(IDL.Nat8))], ['query']),
+    'get_cooldown' : IDL.Func([IDL.Opt(IDL.Text)], [Result_5], ['query']),
     'get_history' : IDL.Func(
-        [IDL.Vec(IDL.Nat8)],
+        [IDL.Vec(IDL.Nat8), IDL.Opt(IDL.Text)],
         [IDL.Vec(IDL.Nat32)],
         ['query'],
       ),
     'get_jurors' : IDL.Func(
         [IDL.Nat32],
-        [IDL.Vec(IDL.Vec(IDL.Nat8))],
+        [Result_6],
         ['query'],
       ),
+    'get_panel' : IDL.Func([IDL.Nat32], [Result_6], ['query']),
     'get_pending' : IDL.Func([], [IDL.Nat32], ['query']),
     'get_pool' : IDL.Func(
-        [IDL.Nat32, IDL.Nat32, IDL.Nat32],
-        [IDL.Vec(IDL.Vec(IDL.Nat8))],
+        [IDL.Nat32, IDL.Nat32, IDL.Nat32, IDL.Opt(IDL.Text)],
+        [Result_6],
+        ['query'],
+      ),
+    'get_pool_size' : IDL.Func(
+        [IDL.Nat32, IDL.Opt(IDL.Text)],
+        [Result],
         ['query'],
       ),
-    'get_pool_size' : IDL.Func([IDL.Nat32], [IDL.Nat32], ['query']),
-    'get_size' : IDL.Func([IDL.Nat32], [IDL.Nat32], ['query']),
+    'get_pools' : IDL.Func([], [IDL.Vec(IDL.Text)], ['query']),
+    'get_size' : IDL.Func([IDL.Nat32], [Result], ['query']),
     'length' : IDL.Func([], [IDL.Nat32], ['query']),
+    'promote_alternate' : IDL.Func(
+        [IDL.Nat32, IDL.Opt(IDL.Vec(IDL.Nat8)), IDL.Vec(IDL.Nat8)],
+        [Result],
+        [],
+      ),
     'remove' : IDL.Func(
-        [IDL.Vec(IDL.Vec(IDL.Nat8)), IDL.Vec(IDL.Nat8)],
-        [IDL.Nat32],
+        [IDL.Vec(IDL.Vec(IDL.Nat8)), IDL.Vec(IDL.Nat8), IDL.Opt(IDL.Text)],
+        [Result],
+        [],
+      ),
+    'replace' : IDL.Func(
+        [IDL.Nat32, IDL.Vec(IDL.Vec(IDL.Nat8)), IDL.Vec(IDL.Nat8)],
+        [Result],
         [],
       ),
     'select' : IDL.Func(
-        [IDL.Nat32, IDL.Nat32, IDL.Vec(IDL.Nat8)],
-        [IDL.Nat32],
+        [IDL.Nat32, IDL.Nat32, IDL.Vec(IDL.Nat8), IDL.Opt(SelectOptions)],
+        [Result],
+        [],
+      ),
+    'select_stratified' : IDL.Func(
+        [IDL.Nat32, IDL.Vec(Quota), IDL.Vec(IDL.Nat8), IDL.Opt(SelectOptions)],
+        [Result],
+        [],
+      ),
+    'select_weighted' : IDL.Func(
+        [IDL.Nat32, IDL.Nat32, IDL.Vec(IDL.Nat8), IDL.Opt(SelectOptions)],
+        [Result],
+        [],
+      ),
+    'set_cooldown' : IDL.Func(
+        [IDL.Opt(Cooldown), IDL.Opt(IDL.Text)],
+        [Result_7],
+        [],
+      ),
+    'strike' : IDL.Func(
+        [
+          IDL.Nat32,
+          IDL.Vec(IDL.Vec(IDL.Nat8)),
+          IDL.Principal,
+          IDL.Vec(IDL.Nat8),
+        ],
+        [Result],
         [],
       ),
   });
 };
-export const init = ({ IDL }) => { return [IDL.Opt(IDL.Text)]; };
+export const init = ({ IDL }) => { return [IDL.Opt(IDL.Text), IDL.Opt(IDL.Text)]; };
@@ -1,84 +1,194 @@
+use block::{Block, Cooldown, Data, Kind, Quota, Role};
 use candid::{CandidType, Decode, Deserialize, Encode, Principal};
-use hash_tree::{HashTree, LookupResult};
+use certificate::{Certificate, IC_ROOT_KEY};
 use ic_certified_map::{AsHashTree, Hash, RbTree};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::{
     cell::Cell as StableCell, log::Log, BoundedStorable, DefaultMemoryImpl, StableBTreeMap,
     Storable,
 };
-use rand::distributions::Distribution;
-use rand::distributions::Uniform;
-use rand::Rng;
-use rand_core::SeedableRng;
+use num_traits::FromPrimitive;
+use pool::Pool;
 use serde::Serialize;
 use sha2::Digest;
-use std::collections::{HashMap, HashSet};
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::{borrow::Cow, cell::RefCell};
 #[macro_use]
 extern crate num_derive;
 
+mod api;
+mod block;
+mod certificate;
 mod hash_tree;
+mod pool;
+mod selection;
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 type Blob = Vec<u8>;
 type History = Vec<u32>;
-type PoolTree = RbTree<Blob, Blob>;
+type PoolHistoryKey = ((PoolStorable, JurorHash), u32);
 type BlockTree = RbTree<Blob, Hash>;
 
-const MAX_HISTORY: usize = 8;
+const MAX_POOL_ID_SIZE: usize = 64;
+const MAX_CATEGORY_SIZE: usize = 64;
+// Juror secrets are 32 bytes so that they can not be guessed from the
+// commitments sha256(secret) recorded in Add blocks.
+const SECRET_SIZE: usize = 32;
+// Selection algorithm recorded in new Select blocks, see sample().
+const SAMPLE_ALGORITHM: u32 = 2;
+// Bound on the encoded blocks returned by get_blocks(), under the 2MiB reply limit.
+const MAX_BLOCKS_SIZE: usize = 1 << 20;
+// Certificates older than this (in nanoseconds) are rejected by commit().
+const MAX_CERTIFICATE_AGE: u64 = 5 * 60 * 1_000_000_000;
 
-#[derive(Clone, Debug, Default, CandidType, Deserialize, FromPrimitive)]
-enum Kind {
-    #[default]
-    Add,
-    Remove,
-    Select,
-    Expand,
-}
+// Roles are stored in AUTH as a bit set indexed by the Role value.
+const ALL_ROLES: u32 = (1 << (Role::AuthAdmin as u32 + 1)) - 1;
+// The AUTH value of the single Admin role of the first version.
+const LEGACY_ADMIN: u32 = 0;
 
-#[derive(Clone, Debug, Default, CandidType, Deserialize)]
-struct Data {
-    kind: Kind,
-    jurors: Vec<Blob>,
-    rand: Option<Blob>,
-    memo: Blob,
+#[derive(Clone, Debug, CandidType, Deserialize)]
+struct Authorization {
+    id: Principal,
+    roles: Vec<Role>,
 }
 
+// The options of select, select_weighted and select_stratified.
 #[derive(Clone, Debug, Default, CandidType, Deserialize)]
-struct Block {
-    certificate: Blob,
-    tree: Blob,
-    data: Data,
-    previous_hash: Hash,
+struct SelectOptions {
+    // The pool to draw from, None for the default pool.
+    pool: Option<String>,
+    // Jurors to leave out of the draw.
+    exclude: Option<Vec<Blob>>,
+    // Whether to leave out the jurors serving on the open juries of the pool.
+    exclude_serving: Option<bool>,
+    // The number of jurors each party may strike.
+    strikes: Option<u32>,
+    // The number of alternates to draw after the jurors, none if stratified.
+    alternates: Option<u32>,
 }
 
-#[derive(Clone, Debug, CandidType, Deserialize, FromPrimitive)]
-enum Auth {
-    Admin,
+#[derive(Clone, Debug, CandidType, Deserialize)]
+enum JuryError {
+    // The log index is not less than length().
+    IndexOutOfRange {
+        index: u32,
+        length: u32,
+    },
+    // The block at the log index is not a Select, Expand or Replace block.
+    NotASelection(u32),
+    // The pool has fewer jurors than requested.
+    PoolTooSmall {
+        size: u32,
+        count: u32,
+    },
+    // The pool id is larger than MAX_POOL_ID_SIZE bytes.
+    PoolIdTooLarge(String),
+    // The number of secrets is not the number of jurors.
+    SecretCount {
+        jurors: u32,
+        secrets: u32,
+    },
+    // The number of weights is not the number of jurors.
+    WeightCount {
+        jurors: u32,
+        weights: u32,
+    },
+    // The number of categories is not the number of jurors.
+    CategoryCount {
+        jurors: u32,
+        categories: u32,
+    },
+    // The category is larger than MAX_CATEGORY_SIZE bytes.
+    CategoryTooLarge(String),
+    // The category has more than one quota.
+    DuplicateCategory(String),
+    // The pool has fewer jurors of the category than its quota.
+    StratumTooSmall {
+        category: String,
+        size: u32,
+        count: u32,
+    },
+    // The selection at the log index is stratified and can not be expanded.
+    NotExpandable(u32),
+    // Stratified selections have no alternates.
+    StratifiedAlternates,
+    // The jury of the Select, Expand or Replace block at the log index is closed.
+    JuryClosed(u32),
+    // The juror to replace or strike is not on the panel of the jury.
+    NotOnPanel(Blob),
+    // There are no jurors to replace or strike.
+    NoJurors,
+    // The party would strike more jurors from the jury than its Select allows.
+    StrikeLimit {
+        party: Principal,
+        limit: u32,
+    },
+    // The jury of the block at the log index has no alternate left to seat.
+    NoAlternates(u32),
+    // The secret of the juror at this position is not SECRET_SIZE bytes.
+    InvalidSecret(u32),
+    // The certificate could not be decoded.
+    MalformedCertificate(String),
+    // The certificate does not certify the staged blocks.
+    CertificateMismatch,
+    // The certificate is not signed by the root key.
+    InvalidCertificate(String),
+    // The certificate time is more than MAX_CERTIFICATE_AGE before now.
+    CertificateTooOld(u64),
+    // The caller does not have the Role required by this method.
+    Unauthorized,
+    // The principal is not authorized.
+    UnknownPrincipal(Principal),
+    // The management canister did not provide randomness.
+    Randomness(String),
 }
 
-#[derive(Clone, Debug, CandidType, Deserialize)]
-struct Authorization {
-    id: Principal,
-    auth: Auth,
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+struct Blocks {
+    blocks: Vec<Block>,
+    // The start of the next page or None if there are no more committed blocks
+    // or none were requested.
+    next: Option<u32>,
 }
 
 #[derive(Clone, Debug, Default, CandidType, Deserialize)]
 struct StoreHash(Hash);
 
+// A committed block without the certificate and tree of its commit.
 #[derive(Clone, Debug, Default, CandidType, Deserialize)]
-struct StoreData(Vec<Data>);
+struct StoreBlock {
+    commit: u32,
+    data: Data,
+}
 
-#[allow(dead_code)]
-#[derive(Deserialize)]
-struct ReplicaCertificate {
-    tree: HashTree<'static>,
-    signature: serde_bytes::ByteBuf,
+// The certificate and tree shared by the blocks of a commit.
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+struct StoreCommit {
+    certificate: Blob,
+    tree: Blob,
+    previous_hash: Hash,
 }
 
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+struct StoreData(Vec<Data>);
+
+// An IC root key, DER encoded, or empty for the mainnet key.
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+struct StoreKey(Blob);
+
 impl Storable for StoreHash {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+}
+
+impl Storable for StoreKey {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         Cow::Owned(Encode!(self).unwrap())
     }
 
@@ -88,7 +198,7 @@ impl Storable for StoreHash {
 }
 
 impl Storable for StoreData {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         Cow::Owned(Encode!(self).unwrap())
     }
 
@@ -101,7 +211,7 @@ impl Storable for StoreData {
 struct PrincipalStorable(Principal);
 
 impl Storable for PrincipalStorable {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         std::borrow::Cow::from(self.0.as_slice())
     }
 
@@ -115,15 +225,191 @@ impl BoundedStorable for PrincipalStorable {
     const IS_FIXED_SIZE: bool = false;
 }
 
+// The sha256 of a juror, so that jurors of any size key the stable maps.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Default)]
+struct JurorHash(Hash);
+
+impl Storable for JurorHash {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::from(&self.0[..])
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Self(bytes[..].try_into().unwrap())
+    }
+}
+
+impl BoundedStorable for JurorHash {
+    const MAX_SIZE: u32 = 32;
+    const IS_FIXED_SIZE: bool = true;
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Default)]
+struct PoolStorable(String);
+
+impl Storable for PoolStorable {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::from(self.0.as_bytes())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Self(String::from_utf8(bytes.to_vec()).unwrap())
+    }
+}
+
+impl BoundedStorable for PoolStorable {
+    const MAX_SIZE: u32 = MAX_POOL_ID_SIZE as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(Clone, Default)]
+struct SecretStorable(Blob);
+
+impl Storable for SecretStorable {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::from(self.0.as_slice())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl BoundedStorable for SecretStorable {
+    const MAX_SIZE: u32 = SECRET_SIZE as u32;
+    const IS_FIXED_SIZE: bool = true;
+}
+
+#[derive(Clone, Default)]
+struct CategoryStorable(String);
+
+impl Storable for CategoryStorable {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::from(self.0.as_bytes())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Self(String::from_utf8(bytes.to_vec()).unwrap())
+    }
+}
+
+impl BoundedStorable for CategoryStorable {
+    const MAX_SIZE: u32 = MAX_CATEGORY_SIZE as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// A Cooldown as a variant byte followed by the little endian amount.
+struct CooldownStorable(Cooldown);
+
+impl Storable for CooldownStorable {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        let (variant, amount) = match self.0 {
+            Cooldown::Blocks(blocks) => (0, blocks as u64),
+            Cooldown::Nanoseconds(nanoseconds) => (1, nanoseconds),
+        };
+        let mut bytes = vec![variant];
+        bytes.extend_from_slice(&amount.to_le_bytes());
+        std::borrow::Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        let amount = u64::from_le_bytes(bytes[1..].try_into().unwrap());
+        Self(match bytes[0] {
+            0 => Cooldown::Blocks(amount as u32),
+            _ => Cooldown::Nanoseconds(amount),
+        })
+    }
+}
+
+impl BoundedStorable for CooldownStorable {
+    const MAX_SIZE: u32 = 9;
+    const IS_FIXED_SIZE: bool = true;
+}
+
 thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
         RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+    // Blocks committed by the first version with the certificate and tree in
+    // every block, moved to BLOCKS and COMMITS on upgrade.
     static LOG: RefCell<Log<Vec<u8>, Memory, Memory>> = RefCell::new(
         Log::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))),
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
             ).unwrap()
         );
+    // Committed blocks (StoreBlock) by log index.
+    static BLOCKS: RefCell<Log<Vec<u8>, Memory, Memory>> = RefCell::new(
+        Log::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))),
+            ).unwrap()
+        );
+    // Commits (StoreCommit) referenced by BLOCKS.
+    static COMMITS: RefCell<Log<Vec<u8>, Memory, Memory>> = RefCell::new(
+        Log::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))),
+            ).unwrap()
+        );
+    // Map from ((pool, sha256(juror)), index) to (): add index, (delete index, (add index ...))
+    static POOL_HISTORY: RefCell<StableBTreeMap<PoolHistoryKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17))))
+        );
+    // The (pool, juror) of each juror in the order first added to the pool, as
+    // POOL_HISTORY only has the hash of the juror.
+    static JURORS: RefCell<Log<Vec<u8>, Memory, Memory>> = RefCell::new(
+        Log::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19))),
+            ).unwrap()
+        );
+    // Map from ((pool, sha256(juror)), add index) to the secret the juror was
+    // added with.  The secrets are not in the log and so can not be rebuilt.
+    static SECRETS: RefCell<StableBTreeMap<PoolHistoryKey, SecretStorable, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23))))
+        );
+    // Map from ((pool, sha256(juror)), add index) to the weight the juror was added with.
+    static WEIGHTS: RefCell<StableBTreeMap<PoolHistoryKey, u32, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21))))
+        );
+    // Map from ((pool, sha256(juror)), add index) to the category the juror was added with.
+    static CATEGORIES: RefCell<StableBTreeMap<PoolHistoryKey, CategoryStorable, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22))))
+        );
+    // Map from pool to the cooldown applied by its selections.
+    static COOLDOWNS: RefCell<StableBTreeMap<PoolStorable, CooldownStorable, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15))))
+        );
+    // Map from (Select index, Select, Expand, Replace, Strike or Promote index)
+    // to () for the blocks of every jury, open or closed.
+    static JURIES: RefCell<StableBTreeMap<(u32, u32), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16))))
+        );
+    // Map from (pool, Select index) to () for the open juries of each pool.  The
+    // juries selected by the first version, which could not close them, are
+    // closed.
+    static OPEN_JURIES: RefCell<StableBTreeMap<(PoolStorable, u32), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(24))))
+        );
+    // Map from (pool, u32::MAX - index) to () for the Select, Expand and Replace
+    // blocks drawing from each pool, so that a pool's range is newest first.
+    static SELECTIONS: RefCell<StableBTreeMap<(PoolStorable, u32), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(25))))
+        );
+    // Each pool at each log index, rebuilt from POOL_HISTORY on upgrade.
+    static POOLS: RefCell<BTreeMap<String, Pool>> = const { RefCell::new(BTreeMap::new()) };
+    // The jurors of each category ("" for none) of each pool at each log index,
+    // the strata of stratified selections, rebuilt with POOLS.
+    static STRATA: RefCell<BTreeMap<(String, String), Pool>> =
+        const { RefCell::new(BTreeMap::new()) };
     static AUTH: RefCell<StableBTreeMap<PrincipalStorable, u32, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))))
@@ -131,11 +417,14 @@ thread_local! {
     static PENDING_DATA: RefCell<StableCell<StoreData, Memory>> = RefCell::new(StableCell::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))),
             StoreData::default()).unwrap());
+    // The hash of the last committed block (see block_hash()), the previous_hash
+    // of the next commit, or the hash passed to canister_init() before the first.
     static PREVIOUS_HASH: RefCell<StableCell<StoreHash, Memory>> = RefCell::new(StableCell::init(
           MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))),
           <StoreHash>::default()).unwrap());
-    // Map from juror to history: add index, (delete index, (add index ...))
-    static TREE: RefCell<PoolTree> = RefCell::new(RbTree::new());
+    static ROOT_KEY: RefCell<StableCell<StoreKey, Memory>> = RefCell::new(StableCell::init(
+          MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10))),
+          <StoreKey>::default()).unwrap());
 }
 
 fn set_certificate() -> Blob {
@@ -143,22 +432,72 @@ fn set_certificate() -> Blob {
     let previous_hash = get_previous_hash();
     let hash = build_tree(&data, &previous_hash).root_hash();
     let certified_data = &ic_certified_map::labeled_hash(b"jury_blocks", &hash);
-    ic_cdk::api::set_certified_data(certified_data);
+    api::set_certified_data(certified_data);
     certified_data.to_vec()
 }
 
-fn from_history(history: &History) -> Vec<u8> {
-    history
-        .iter()
-        .map(|h| h.to_le_bytes().to_vec())
-        .flatten()
-        .collect()
+// The (pool, juror) part of the keys of POOL_HISTORY.
+fn juror_key(pool: &str, juror: &[u8]) -> (PoolStorable, JurorHash) {
+    let hash = sha2::Sha256::digest(juror).into();
+    (PoolStorable(pool.to_string()), JurorHash(hash))
 }
 
-fn to_history(b: &Blob) -> History {
-    b.chunks_exact(4)
-        .map(|h| u32::from_le_bytes(h.try_into().unwrap()))
-        .collect()
+fn juror_history(pool: &str, juror: &Blob) -> History {
+    if pool.len() > MAX_POOL_ID_SIZE {
+        return Vec::new();
+    }
+    let key = juror_key(pool, juror);
+    let start = (key.clone(), 0);
+    let end = (key, u32::MAX);
+    POOL_HISTORY.with(|h| h.borrow().range(start..=end).map(|(k, _)| k.1).collect())
+}
+
+// The pool id of the default pool is "", which is recorded as None.
+fn pool_id(pool: Option<String>) -> Result<Option<String>, JuryError> {
+    match pool {
+        Some(pool) if pool.len() > MAX_POOL_ID_SIZE => Err(JuryError::PoolIdTooLarge(pool)),
+        Some(pool) if pool.is_empty() => Ok(None),
+        pool => Ok(pool),
+    }
+}
+
+// Apply f to the pool, which is empty if nothing was ever added to it.
+fn with_pool<R>(pool: &str, f: impl FnOnce(&Pool) -> R) -> R {
+    POOLS.with(|p| match p.borrow().get(pool) {
+        Some(p) => f(p),
+        None => f(&Pool::new()),
+    })
+}
+
+// Apply f to the strata of the categories of the quotas in the pool, in the
+// same order.
+fn with_strata<R>(pool: &str, quotas: &[Quota], f: impl FnOnce(&[&Pool]) -> R) -> R {
+    let empty = Pool::new();
+    STRATA.with(|s| {
+        let s = s.borrow();
+        let strata: Vec<&Pool> = quotas
+            .iter()
+            .map(|q| {
+                s.get(&(pool.to_string(), q.category.clone()))
+                    .unwrap_or(&empty)
+            })
+            .collect();
+        f(&strata)
+    })
+}
+
+fn insert_history(pool: &str, juror: &Blob, index: u32) {
+    let key = juror_key(pool, juror);
+    let first = POOL_HISTORY.with(|h| {
+        let mut h = h.borrow_mut();
+        let first = h.range((key.clone(), 0)..).next().map(|(k, _)| k.0) != Some(key.clone());
+        h.insert((key, index), ());
+        first
+    });
+    if first {
+        let juror = Encode!(&pool, juror).unwrap();
+        JURORS.with(|j| j.borrow().append(&juror).unwrap());
+    }
 }
 
 fn push_pending(data: &Data) {
@@ -169,158 +508,936 @@ fn push_pending(data: &Data) {
     });
 }
 
-#[ic_cdk_macros::update(guard = "is_authorized")]
+fn add_to_pool(
+    pool: &str,
+    index: u32,
+    jurors: Vec<Blob>,
+    secrets: Option<Vec<Option<Blob>>>,
+    weights: Option<Vec<u32>>,
+    categories: Option<Vec<String>>,
+) {
+    for (i, j) in jurors.into_iter().enumerate() {
+        if juror_history(pool, &j).len() % 2 == 1 {
+            // Already inserted, do nothing.
+            continue;
+        }
+        // Currently deleted or new, (re)insert.
+        let weight = weights.as_ref().and_then(|w| w.get(i)).copied();
+        POOLS.with(|p| {
+            p.borrow_mut().entry(pool.to_string()).or_default().insert(
+                index,
+                &j,
+                weight.unwrap_or(1),
+            )
+        });
+        let key = juror_key(pool, &j);
+        if let Some(s) = secrets.as_ref().and_then(|s| s.get(i).cloned().flatten()) {
+            let key = (key.clone(), index);
+            SECRETS.with(|m| m.borrow_mut().insert(key, SecretStorable(s)));
+        }
+        if let Some(w) = weight {
+            WEIGHTS.with(|m| m.borrow_mut().insert((key.clone(), index), w));
+        }
+        let category = categories.as_ref().and_then(|c| c.get(i)).cloned();
+        let category = category.unwrap_or_default();
+        STRATA.with(|s| {
+            s.borrow_mut()
+                .entry((pool.to_string(), category.clone()))
+                .or_default()
+                .insert(index, &j, weight.unwrap_or(1))
+        });
+        if !category.is_empty() {
+            let c = CategoryStorable(category);
+            CATEGORIES.with(|m| m.borrow_mut().insert((key, index), c));
+        }
+        insert_history(pool, &j, index);
+    }
+}
+
+// The POOL_HISTORY key of the Add of a juror in the pool at index, if any.
+fn added_key(pool: &str, juror: &Blob, index: u32) -> Option<PoolHistoryKey> {
+    let history = juror_history(pool, juror);
+    let changes = history.iter().filter(|i| **i <= index).count();
+    if changes % 2 == 0 {
+        return None;
+    }
+    Some((juror_key(pool, juror), history[changes - 1]))
+}
+
+// The secret of a juror in the pool at index, if it was added with one.
+fn juror_secret(pool: &str, juror: &Blob, index: u32) -> Option<Blob> {
+    let key = added_key(pool, juror, index)?;
+    SECRETS.with(|m| m.borrow().get(&key).map(|s| s.0))
+}
+
+// The category of a juror in the pool at index, "" if it was added without one.
+fn juror_category(pool: &str, juror: &Blob, index: u32) -> String {
+    added_key(pool, juror, index)
+        .and_then(|key| CATEGORIES.with(|m| m.borrow().get(&key)))
+        .map(|c| c.0)
+        .unwrap_or_default()
+}
+
+// The blinded ids of jurors drawn with rand from the pool at index.
+fn blind(pool: &str, index: u32, rand: &[u8], jurors: &[Blob]) -> Vec<Blob> {
+    let secrets: Vec<Option<Blob>> = jurors
+        .iter()
+        .map(|j| juror_secret(pool, j, index))
+        .collect();
+    selection::blind(rand, &secrets)
+}
+
+fn remove_from_pool(pool: &str, index: u32, jurors: Vec<Blob>) {
+    for j in jurors {
+        if juror_history(pool, &j).len() % 2 == 1 {
+            let stratum = (pool.to_string(), juror_category(pool, &j, index));
+            POOLS.with(|p| p.borrow_mut().get_mut(pool).unwrap().remove(index, &j));
+            STRATA.with(|s| s.borrow_mut().get_mut(&stratum).unwrap().remove(index, &j));
+            insert_history(pool, &j, index);
+        } else {
+            // Already deleted or never added, do nothing.
+        }
+    }
+}
+
+// Replay the Add and Remove blocks in the log and pending data to rebuild POOL_HISTORY.
+fn rebuild_pool() {
+    let committed = committed();
+    for index in 0..committed {
+        apply_to_pool(index, read_data(index));
+    }
+    let pending = PENDING_DATA.with(|d| d.borrow().get().0.clone());
+    for (i, data) in pending.into_iter().enumerate() {
+        apply_to_pool(committed + i as u32, data);
+    }
+}
+
+fn apply_to_pool(index: u32, data: Data) {
+    let pool = data.pool.unwrap_or_default();
+    match data.kind {
+        // The secrets of the jurors are not in the log.
+        Kind::Add => add_to_pool(
+            &pool,
+            index,
+            data.jurors,
+            None,
+            data.weights,
+            data.categories,
+        ),
+        Kind::Remove => remove_from_pool(&pool, index, data.jurors),
+        _ => (),
+    }
+}
+
+#[ic_cdk_macros::update]
 #[candid::candid_method]
-fn add(new_jurors: Vec<Blob>, memo: Blob) -> u32 {
-    let mut new_data = Data::default();
-    new_data.kind = Kind::Add;
-    new_data.jurors = new_jurors.clone();
-    new_data.memo = memo;
+fn add(
+    new_jurors: Vec<Blob>,
+    memo: Blob,
+    pool: Option<String>,
+    secrets: Option<Vec<Option<Blob>>>,
+    weights: Option<Vec<u32>>,
+    categories: Option<Vec<String>>,
+) -> Result<u32, JuryError> {
+    is_authorized(Role::PoolManager)?;
+    if let Some(secrets) = &secrets {
+        if secrets.len() != new_jurors.len() {
+            return Err(JuryError::SecretCount {
+                jurors: new_jurors.len() as u32,
+                secrets: secrets.len() as u32,
+            });
+        }
+        if let Some(i) = secrets
+            .iter()
+            .position(|s| s.as_ref().is_some_and(|s| s.len() != SECRET_SIZE))
+        {
+            return Err(JuryError::InvalidSecret(i as u32));
+        }
+    }
+    if let Some(weights) = &weights {
+        if weights.len() != new_jurors.len() {
+            return Err(JuryError::WeightCount {
+                jurors: new_jurors.len() as u32,
+                weights: weights.len() as u32,
+            });
+        }
+    }
+    if let Some(categories) = &categories {
+        if categories.len() != new_jurors.len() {
+            return Err(JuryError::CategoryCount {
+                jurors: new_jurors.len() as u32,
+                categories: categories.len() as u32,
+            });
+        }
+        if let Some(c) = categories.iter().find(|c| c.len() > MAX_CATEGORY_SIZE) {
+            return Err(JuryError::CategoryTooLarge(c.clone()));
+        }
+    }
+    let pool = pool_id(pool)?;
+    let new_data = Data {
+        kind: Kind::Add,
+        jurors: new_jurors.clone(),
+        memo,
+        pool: pool.clone(),
+        commitments: secrets.as_ref().map(|s| {
+            s.iter()
+                .map(|s| s.as_deref().map(selection::commitment))
+                .collect()
+        }),
+        weights: weights.clone(),
+        categories: categories.clone(),
+        ..Default::default()
+    };
     push_pending(&new_data);
     let index = length() - 1;
-    TREE.with(|t| {
-        let mut t = t.borrow_mut();
-        for j in new_jurors {
-            if let Some(history) = t.get(&j) {
-                let mut history = to_history(history);
-                if history.len() % 2 == 0 {
-                    // Currently deleted, reinsert.
-                    if history.len() >= MAX_HISTORY {
-                        ic_cdk::trap(&format!(
-                            "exceeded MAX_HISTORY({}) changes for juror: {:?}",
-                            MAX_HISTORY, j
-                        ));
-                    }
-                    history.push(index);
-                    t.insert(j, from_history(&history));
-                } else {
-                    // Already inserted, do nothing.
-                }
-            } else {
-                t.insert(j, from_history(&vec![index]));
+    add_to_pool(
+        &pool.unwrap_or_default(),
+        index,
+        new_jurors,
+        secrets,
+        weights,
+        categories,
+    );
+    set_certificate();
+    Ok(index)
+}
+
+#[ic_cdk_macros::update]
+#[candid::candid_method]
+fn remove(remove_jurors: Vec<Blob>, memo: Blob, pool: Option<String>) -> Result<u32, JuryError> {
+    is_authorized(Role::PoolManager)?;
+    let pool = pool_id(pool)?;
+    let new_data = Data {
+        kind: Kind::Remove,
+        jurors: remove_jurors.clone(),
+        memo,
+        pool: pool.clone(),
+        ..Default::default()
+    };
+    push_pending(&new_data);
+    let index = length() - 1;
+    remove_from_pool(&pool.unwrap_or_default(), index, remove_jurors);
+    set_certificate();
+    Ok(index)
+}
+
+// Replay POOL_HISTORY in log order to rebuild POOLS and STRATA.
+fn load_pool() {
+    // (index, weight of an Add or None for a Remove, pool, category, juror)
+    let mut changes: Vec<(u32, Option<u32>, String, String, Blob)> = Vec::new();
+    for i in 0..JURORS.with(|j| j.borrow().len()) {
+        let juror = JURORS.with(|j| j.borrow().get(i).unwrap());
+        let (pool, juror) = Decode!(&juror, String, Blob).unwrap();
+        let key = juror_key(&pool, &juror);
+        let mut category = String::new();
+        for (n, index) in juror_history(&pool, &juror).into_iter().enumerate() {
+            let added = (key.clone(), index);
+            // The weight of an Add, 1 if it was added without one.
+            let weight = (n % 2 == 0).then(|| {
+                category = CATEGORIES
+                    .with(|m| m.borrow().get(&added))
+                    .map(|c| c.0)
+                    .unwrap_or_default();
+                WEIGHTS.with(|m| m.borrow().get(&added)).unwrap_or(1)
+            });
+            changes.push((index, weight, pool.clone(), category.clone(), juror.clone()));
+        }
+    }
+    changes.sort_by_key(|c| c.0);
+    for (index, weight, pool, category, juror) in changes {
+        POOLS.with(|p| {
+            let mut p = p.borrow_mut();
+            let pool = p.entry(pool.clone()).or_default();
+            match weight {
+                Some(weight) => pool.insert(index, &juror, weight),
+                None => pool.remove(index, &juror),
+            }
+        });
+        STRATA.with(|s| {
+            let mut s = s.borrow_mut();
+            let stratum = s.entry((pool, category)).or_default();
+            match weight {
+                Some(weight) => stratum.insert(index, &juror, weight),
+                None => stratum.remove(index, &juror),
+            }
+        });
+    }
+}
+
+fn make_jury(
+    pool: &str,
+    index: u32,
+    count: u32,
+    seed: Hash,
+    algorithm: Option<u32>,
+    exclude: &[Blob],
+) -> Result<Vec<Blob>, JuryError> {
+    with_pool(pool, |p| {
+        selection::make_jury(p, index, count, seed, algorithm, exclude)
+    })
+    .ok_or_else(|| JuryError::PoolTooSmall {
+        size: available(pool, index, exclude),
+        count,
+    })
+}
+
+// The number of jurors in the pool at index which are not excluded.
+fn available(pool: &str, index: u32, exclude: &[Blob]) -> u32 {
+    with_pool(pool, |p| {
+        p.size(index) - selection::excluded_ranks(p, index, exclude).len() as u32
+    })
+}
+
+fn check_index(index: u32) -> Result<(), JuryError> {
+    let length = length();
+    if index >= length {
+        return Err(JuryError::IndexOutOfRange { index, length });
+    }
+    Ok(())
+}
+
+#[ic_cdk_macros::update]
+#[candid::candid_method]
+async fn select(
+    index: u32,
+    count: u32,
+    memo: Blob,
+    options: Option<SelectOptions>,
+) -> Result<u32, JuryError> {
+    select_jury(
+        index,
+        count,
+        memo,
+        options.unwrap_or_default(),
+        SAMPLE_ALGORITHM,
+    )
+    .await
+}
+
+// Select in proportion to the weights the jurors were added with.
+#[ic_cdk_macros::update]
+#[candid::candid_method]
+async fn select_weighted(
+    index: u32,
+    count: u32,
+    memo: Blob,
+    options: Option<SelectOptions>,
+) -> Result<u32, JuryError> {
+    select_jury(
+        index,
+        count,
+        memo,
+        options.unwrap_or_default(),
+        selection::WEIGHTED_ALGORITHM,
+    )
+    .await
+}
+
+// Select the jurors of each quota from the jurors of its category.
+#[ic_cdk_macros::update]
+#[candid::candid_method]
+async fn select_stratified(
+    index: u32,
+    quotas: Vec<Quota>,
+    memo: Blob,
+    options: Option<SelectOptions>,
+) -> Result<u32, JuryError> {
+    let options = options.unwrap_or_default();
+    if options.alternates.unwrap_or(0) > 0 {
+        return Err(JuryError::StratifiedAlternates);
+    }
+    let check = |pool: &str, exclude: &[Blob]| {
+        let sizes = strata_sizes(pool, index, &quotas, exclude);
+        for (i, (quota, size)) in quotas.iter().zip(sizes).enumerate() {
+            if quotas[..i].iter().any(|q| q.category == quota.category) {
+                return Err(JuryError::DuplicateCategory(quota.category.clone()));
+            }
+            if quota.count > size {
+                return Err(JuryError::StratumTooSmall {
+                    category: quota.category.clone(),
+                    size,
+                    count: quota.count,
+                });
             }
         }
+        Ok(())
+    };
+    let draw = |pool: &str, seed: Hash, excluded: &[Blob]| {
+        let jurors = with_strata(pool, &quotas, |strata| {
+            selection::make_stratified_jury(strata, index, seed, &quotas, excluded)
+        })
+        .ok_or_else(|| JuryError::PoolTooSmall {
+            size: available(pool, index, excluded),
+            count: quotas.iter().map(|q| q.count).sum(),
+        })?;
+        Ok(Data {
+            algorithm: Some(selection::STRATIFIED_ALGORITHM),
+            jurors,
+            quotas: Some(quotas.clone()),
+            ..Default::default()
+        })
+    };
+    stage_select(index, memo, options, check, draw).await
+}
+
+// The cooldown applied by selections from the pool.
+fn pool_cooldown(pool: &str) -> Option<Cooldown> {
+    COOLDOWNS.with(|c| c.borrow().get(&PoolStorable(pool.to_string())).map(|c| c.0))
+}
+
+// Record the Select, Expand or Replace block at index drawing from the pool.
+fn add_selection(pool: &Option<String>, index: u32) {
+    let key = (
+        PoolStorable(pool.clone().unwrap_or_default()),
+        u32::MAX - index,
+    );
+    SELECTIONS.with(|s| s.borrow_mut().insert(key, ()));
+}
+
+// The jurors and alternates drawn from the pool by the Select, Expand and
+// Replace blocks within the cooldown before a selection at index staged at now.
+fn cooling(pool: &Option<String>, cooldown: Option<Cooldown>, index: u32, now: u64) -> Vec<Blob> {
+    let mut jurors = Vec::new();
+    let Some(cooldown) = cooldown else {
+        return jurors;
+    };
+    if index == 0 {
+        return jurors;
+    }
+    // The selections from the pool before index, newest first.
+    let pool = PoolStorable(pool.clone().unwrap_or_default());
+    let start = (pool.clone(), u32::MAX - (index - 1));
+    let end = (pool, u32::MAX);
+    let selections: Vec<u32> = SELECTIONS.with(|s| {
+        s.borrow()
+            .range(start..=end)
+            .map(|((_, i), _)| u32::MAX - i)
+            .collect()
     });
+    for at in selections {
+        let data = read_data(at);
+        if !selection::cooling_down(&cooldown, index, now, at, data.time) {
+            break;
+        }
+        jurors.extend(data.jurors);
+        jurors.extend(data.alternates.unwrap_or_default());
+    }
+    jurors
+}
+
+// The log index and staging time of the Select block extended by the Select
+// or Expand block at index.
+fn selected_at(mut index: u32, data: &Data) -> (u32, u64) {
+    let mut data = data.clone();
+    while let (Kind::Expand, Some(jurors_index)) = (&data.kind, data.jurors_index) {
+        index = jurors_index;
+        data = read_data(index);
+    }
+    (index, data.time.unwrap_or_default())
+}
+
+// The number of jurors of the category of each quota in the pool at index
+// which are not excluded, in the same order.
+fn strata_sizes(pool: &str, index: u32, quotas: &[Quota], exclude: &[Blob]) -> Vec<u32> {
+    with_strata(pool, quotas, |strata| {
+        strata
+            .iter()
+            .map(|s| s.size(index) - selection::excluded_ranks(s, index, exclude).len() as u32)
+            .collect()
+    })
+}
+
+// Select count jurors then the alternates with algorithm.
+async fn select_jury(
+    index: u32,
+    count: u32,
+    memo: Blob,
+    options: SelectOptions,
+    algorithm: u32,
+) -> Result<u32, JuryError> {
+    let alternates = options.alternates.unwrap_or(0);
+    let count = count.saturating_add(alternates);
+    let check = |pool: &str, exclude: &[Blob]| {
+        let size = available(pool, index, exclude);
+        if count > size {
+            return Err(JuryError::PoolTooSmall { size, count });
+        }
+        Ok(())
+    };
+    let draw = |pool: &str, seed: Hash, excluded: &[Blob]| {
+        // The alternates are the last draws.
+        let mut jurors = make_jury(pool, index, count, seed, Some(algorithm), excluded)?;
+        let alternates = jurors.split_off((count - alternates) as usize);
+        Ok(Data {
+            algorithm: Some(algorithm),
+            jurors,
+            alternates: Some(alternates).filter(|a| !a.is_empty()),
+            ..Default::default()
+        })
+    };
+    stage_select(index, memo, options, check, draw).await
+}
+
+// Stage a Select block of the jurors drawn by draw(pool, seed, excluded) from
+// the pool of the options at index less the jurors excluded, cooling down or, if
+// exclude_serving, serving, once check(pool, exclude) has accepted the request.
+// draw() returns the algorithm, jurors and any alternates and quotas of the
+// block.
+async fn stage_select(
+    index: u32,
+    memo: Blob,
+    options: SelectOptions,
+    check: impl FnOnce(&str, &[Blob]) -> Result<(), JuryError>,
+    draw: impl FnOnce(&str, Hash, &[Blob]) -> Result<Data, JuryError>,
+) -> Result<u32, JuryError> {
+    is_authorized(Role::Selector)?;
+    // The pool at an index before length() can not change while awaiting the seed.
+    check_index(index)?;
+    let pool = pool_id(options.pool)?;
+    let pool_id = pool.clone().unwrap_or_default();
+    let exclude = options.exclude.unwrap_or_default();
+    check(&pool_id, &exclude)?;
+    let seed = get_rng_seed().await?;
+    let cooldown = pool_cooldown(&pool_id);
+    let time = api::time();
+    let mut excluded = exclude.clone();
+    excluded.extend(cooling(&pool, cooldown, length(), time));
+    let serving = options
+        .exclude_serving
+        .unwrap_or(false)
+        .then(|| serving(&pool));
+    excluded.extend(serving.clone().unwrap_or_default());
+    let drawn = draw(&pool_id, seed, &excluded)?;
+    // The alternates are blinded with the jurors.
+    let jurors: Vec<Blob> = drawn
+        .jurors
+        .iter()
+        .chain(drawn.alternates.iter().flatten())
+        .cloned()
+        .collect();
+    let new_data = Data {
+        kind: Kind::Select,
+        rand: Some(seed.to_vec()),
+        pool_index: Some(index),
+        blinded: Some(blind(&pool_id, index, &seed, &jurors)),
+        memo,
+        pool,
+        exclude: Some(exclude).filter(|e| !e.is_empty()),
+        cooldown,
+        time: Some(time),
+        serving,
+        strikes: options.strikes,
+        ..drawn
+    };
+    push_pending(&new_data);
+    open_jury(&new_data.pool, length() - 1);
+    add_selection(&new_data.pool, length() - 1);
+    set_certificate();
+    Ok(length() - 1)
+}
+
+#[ic_cdk_macros::update]
+#[candid::candid_method]
+fn expand(index: u32, count: u32, memo: Blob) -> Result<u32, JuryError> {
+    is_authorized(Role::Selector)?;
+    let old = get_data(index)?;
+    if !matches!(old.kind, Kind::Select | Kind::Expand) {
+        return Err(JuryError::NotASelection(index));
+    }
+    if old.algorithm == Some(selection::STRATIFIED_ALGORITHM) {
+        return Err(JuryError::NotExpandable(index));
+    }
+    let seed: Hash = old
+        .rand
+        .as_ref()
+        .and_then(|r| r.as_slice().try_into().ok())
+        .ok_or(JuryError::NotASelection(index))?;
+    let select = open_select(index, &old)?;
+    // Extend with the same algorithm, pool and stream so the new jurors are
+    // the next draws after every juror drawn for the jury, including those
+    // replaced and the alternates, excluding the same jurors and those cooling
+    // down or serving when selected.  Blocks without a pool_index drew from the
+    // pool at index.
+    let (selected, time) = selected_at(index, &old);
+    let exclude = excluded(&old, selected, time);
+    let pool = old.pool.clone().unwrap_or_default();
+    let pool_index = old.pool_index.unwrap_or(index);
+    let old_count = selection::panel(&jury_blocks(select)).1;
+    // No pool has more than u32::MAX jurors.
+    let new_count = old_count
+        .checked_add(count)
+        .ok_or_else(|| JuryError::PoolTooSmall {
+            size: available(&pool, pool_index, &exclude),
+            count: u32::MAX,
+        })?;
+    let jurors = make_jury(&pool, pool_index, new_count, seed, old.algorithm, &exclude)?
+        [old_count as usize..]
+        .to_vec();
+    let new_data = Data {
+        kind: Kind::Expand,
+        rand: old.rand,
+        algorithm: old.algorithm,
+        pool_index: old.pool_index,
+        jurors_index: Some(index),
+        blinded: Some(blind(&pool, pool_index, &seed, &jurors)),
+        jurors,
+        memo,
+        pool: old.pool,
+        exclude: old.exclude,
+        cooldown: old.cooldown,
+        time: Some(api::time()),
+        serving: old.serving,
+        ..Default::default()
+    };
+    push_pending(&new_data);
+    add_to_jury(select, length() - 1);
+    add_selection(&new_data.pool, length() - 1);
+    set_certificate();
+    Ok(length() - 1)
+}
+
+// The jurors excluded by the Select block at index staged at time, or by the
+// Expand and Replace blocks copying its data: those listed, those cooling down
+// and those serving when selected.
+fn excluded(data: &Data, index: u32, time: u64) -> Vec<Blob> {
+    selection::excluded(data, cooling(&data.pool, data.cooldown, index, time))
+}
+
+// Stage a Replace block drawing a replacement for each of jurors_out on the
+// panel of the open jury of the block at index.  The replacements are the next
+// draws of the stream of the jury after all the jurors drawn by its blocks.
+#[ic_cdk_macros::update]
+#[candid::candid_method]
+fn replace(index: u32, jurors_out: Vec<Blob>, memo: Blob) -> Result<u32, JuryError> {
+    is_authorized(Role::Selector)?;
+    let (select, old) = open_jury_of(index)?;
+    if old.algorithm == Some(selection::STRATIFIED_ALGORITHM) {
+        return Err(JuryError::NotExpandable(index));
+    }
+    let seed: Hash = old
+        .rand
+        .as_ref()
+        .and_then(|r| r.as_slice().try_into().ok())
+        .ok_or(JuryError::NotASelection(index))?;
+    if jurors_out.is_empty() {
+        return Err(JuryError::NoJurors);
+    }
+    let (panel, drawn) = selection::panel(&jury_blocks(select));
+    for (i, juror) in jurors_out.iter().enumerate() {
+        if !panel.contains(juror) || jurors_out[..i].contains(juror) {
+            return Err(JuryError::NotOnPanel(juror.clone()));
+        }
+    }
+    let selected = read_data(select);
+    let exclude = excluded(&selected, select, selected.time.unwrap_or_default());
+    let pool = selected.pool.clone().unwrap_or_default();
+    // Select blocks without a pool_index drew from the pool at their index.
+    let pool_index = selected.pool_index.unwrap_or(select);
+    let count = jurors_out.len() as u32;
+    let jurors = make_jury(
+        &pool,
+        pool_index,
+        drawn + count,
+        seed,
+        selected.algorithm,
+        &exclude,
+    )?[drawn as usize..]
+        .to_vec();
+    let new_data = Data {
+        kind: Kind::Replace,
+        rand: selected.rand,
+        algorithm: selected.algorithm,
+        pool_index: selected.pool_index,
+        jurors_index: Some(select),
+        blinded: Some(blind(&pool, pool_index, &seed, &jurors)),
+        jurors,
+        jurors_out: Some(jurors_out),
+        memo,
+        pool: selected.pool,
+        exclude: selected.exclude,
+        cooldown: selected.cooldown,
+        serving: selected.serving,
+        time: Some(api::time()),
+        ..Default::default()
+    };
+    push_pending(&new_data);
+    add_to_jury(select, length() - 1);
+    add_selection(&new_data.pool, length() - 1);
+    set_certificate();
+    Ok(length() - 1)
+}
+
+// Stage a Strike block recording the peremptory strike of jurors by party from
+// the panel of the open jury of the block at index.  Each party may strike up
+// to the strikes of the Select block of the jury.
+#[ic_cdk_macros::update]
+#[candid::candid_method]
+fn strike(index: u32, jurors: Vec<Blob>, party: Principal, memo: Blob) -> Result<u32, JuryError> {
+    is_authorized(Role::Selector)?;
+    let (select, old) = open_jury_of(index)?;
+    let jury = jury_blocks(select);
+    if jurors.is_empty() {
+        return Err(JuryError::NoJurors);
+    }
+    let (panel, _) = selection::panel(&jury);
+    for (i, juror) in jurors.iter().enumerate() {
+        if !panel.contains(juror) || jurors[..i].contains(juror) {
+            return Err(JuryError::NotOnPanel(juror.clone()));
+        }
+    }
+    let limit = read_data(select).strikes.unwrap_or(0);
+    let struck: usize = jury
+        .iter()
+        .filter(|d| matches!(d.kind, Kind::Strike) && d.principal == Some(party))
+        .map(|d| d.jurors.len())
+        .sum();
+    if struck + jurors.len() > limit as usize {
+        return Err(JuryError::StrikeLimit { party, limit });
+    }
+    let new_data = Data {
+        kind: Kind::Strike,
+        jurors,
+        jurors_index: Some(select),
+        principal: Some(party),
+        pool: old.pool,
+        memo,
+        ..Default::default()
+    };
+    push_pending(&new_data);
+    add_to_jury(select, length() - 1);
+    set_certificate();
+    Ok(length() - 1)
+}
+
+// Stage a Promote block seating the next alternate of the open jury of the
+// block at index, in the seat of juror_out if given.  Alternates are seated in
+// the order they were drawn.
+#[ic_cdk_macros::update]
+#[candid::candid_method]
+fn promote_alternate(index: u32, juror_out: Option<Blob>, memo: Blob) -> Result<u32, JuryError> {
+    is_authorized(Role::Selector)?;
+    let (select, old) = open_jury_of(index)?;
+    let jury = jury_blocks(select);
+    if let Some(juror) = &juror_out {
+        if !selection::panel(&jury).0.contains(juror) {
+            return Err(JuryError::NotOnPanel(juror.clone()));
+        }
+    }
+    let alternate = selection::alternates(&jury)
+        .into_iter()
+        .next()
+        .ok_or(JuryError::NoAlternates(index))?;
+    let new_data = Data {
+        kind: Kind::Promote,
+        jurors: vec![alternate],
+        jurors_out: juror_out.map(|juror| vec![juror]),
+        jurors_index: Some(select),
+        pool: old.pool,
+        memo,
+        ..Default::default()
+    };
+    push_pending(&new_data);
+    add_to_jury(select, length() - 1);
     set_certificate();
-    index
+    Ok(length() - 1)
+}
+
+// Stage a Close block closing the jury of the Select, Expand or Replace block
+// at index.
+#[ic_cdk_macros::update]
+#[candid::candid_method]
+fn close(index: u32, memo: Blob) -> Result<u32, JuryError> {
+    is_authorized(Role::Selector)?;
+    let (select, old) = open_jury_of(index)?;
+    let new_data = Data {
+        kind: Kind::Close,
+        jurors_index: Some(select),
+        pool: old.pool,
+        memo,
+        ..Default::default()
+    };
+    push_pending(&new_data);
+    close_jury(&new_data.pool, select);
+    set_certificate();
+    Ok(length() - 1)
+}
+
+// The blocks of the jury of the Select block at index.
+fn jury_blocks(select: u32) -> Vec<Data> {
+    JURIES.with(|j| {
+        j.borrow()
+            .range((select, 0)..=(select, u32::MAX))
+            .map(|((_, index), _)| read_data(index))
+            .collect()
+    })
+}
+
+// The current panel of the jury of the Select, Expand or Replace block at
+// index: its jurors with those replaced by Replace blocks in their seats, less
+// those struck by Strike blocks.
+#[ic_cdk_macros::query]
+#[candid::candid_method]
+fn get_panel(index: u32) -> Result<Vec<Blob>, JuryError> {
+    let data = get_data(index)?;
+    if !matches!(data.kind, Kind::Select | Kind::Expand | Kind::Replace) {
+        return Err(JuryError::NotASelection(index));
+    }
+    let select = jury_of(index, &data).ok_or(JuryError::NotASelection(index))?;
+    Ok(selection::panel(&jury_blocks(select)).0)
+}
+
+// Open the jury of the Select block at index selected from the pool.
+fn open_jury(pool: &Option<String>, select: u32) {
+    add_to_jury(select, select);
+    let key = (PoolStorable(pool.clone().unwrap_or_default()), select);
+    OPEN_JURIES.with(|o| o.borrow_mut().insert(key, ()));
+}
+
+// Add the Select, Expand, Replace, Strike or Promote block at index to the open
+// jury of the Select block.
+fn add_to_jury(select: u32, index: u32) {
+    JURIES.with(|j| j.borrow_mut().insert((select, index), ()));
+}
+
+// Close the jury of the Select block at index, keeping its blocks in JURIES.
+fn close_jury(pool: &Option<String>, select: u32) {
+    let key = (PoolStorable(pool.clone().unwrap_or_default()), select);
+    OPEN_JURIES.with(|o| o.borrow_mut().remove(&key));
+}
+
+// Whether the jury of the Select block at index selected from the pool is open.
+fn is_open(pool: &Option<String>, select: u32) -> bool {
+    let key = (PoolStorable(pool.clone().unwrap_or_default()), select);
+    OPEN_JURIES.with(|o| o.borrow().contains_key(&key))
+}
+
+// The log index of the Select block of the jury of the Select, Expand,
+// Replace, Strike or Promote block at index.
+fn jury_of(index: u32, data: &Data) -> Option<u32> {
+    let select = match data.kind {
+        // Expand blocks of the first version do not record the block they
+        // extend, so find them among the earlier juries.
+        Kind::Expand if data.jurors_index.is_none() => {
+            return JURIES.with(|j| {
+                j.borrow()
+                    .range(..(index, 0))
+                    .find(|((_, i), _)| *i == index)
+                    .map(|((select, _), _)| select)
+            });
+        }
+        Kind::Select | Kind::Expand => selected_at(index, data).0,
+        Kind::Replace | Kind::Strike | Kind::Promote => data.jurors_index?,
+        _ => return None,
+    };
+    JURIES
+        .with(|j| j.borrow().contains_key(&(select, index)))
+        .then_some(select)
+}
+
+// The log index of the Select block of the open jury of the Select, Expand or
+// Replace block at index, and the block's data.
+fn open_jury_of(index: u32) -> Result<(u32, Data), JuryError> {
+    let data = get_data(index)?;
+    if !matches!(data.kind, Kind::Select | Kind::Expand | Kind::Replace) {
+        return Err(JuryError::NotASelection(index));
+    }
+    let select = open_select(index, &data)?;
+    Ok((select, data))
 }
 
-#[ic_cdk_macros::update(guard = "is_authorized")]
-#[candid::candid_method]
-fn remove(remove_jurors: Vec<Blob>, memo: Blob) -> u32 {
-    let mut new_data = Data::default();
-    new_data.kind = Kind::Remove;
-    new_data.jurors = remove_jurors.clone();
-    new_data.memo = memo;
-    push_pending(&new_data);
-    let index = length() - 1;
-    TREE.with(|t| {
-        let mut t = t.borrow_mut();
-        for j in remove_jurors {
-            if let Some(history) = t.get(&j) {
-                let mut history = to_history(history);
-                if history.len() % 2 == 1 {
-                    history.push(index);
-                    t.insert(j, from_history(&history));
-                } else {
-                    // Already deleted, do nothing.
-                }
-            }
-        }
-    });
-    set_certificate();
-    index
-}
-
-fn collect_pool(index: u32) -> Vec<Blob> {
-    TREE.with(|t| {
-        let mut pool = Vec::new();
-        t.borrow().for_each(|k, v| {
-            // check that the juror is active at index
-            let history = to_history(v);
-            let mut found = false;
-            if history.len() % 2 == 1 {
-                // simple case: juror is currently active
-                if history[history.len() - 1] <= index {
-                    pool.push(k.to_vec());
-                    found = true;
-                }
+// The log index of the Select block of the jury of the block at index, if the
+// jury is open.
+fn open_select(index: u32, data: &Data) -> Result<u32, JuryError> {
+    jury_of(index, data)
+        .filter(|select| is_open(&data.pool, *select))
+        .ok_or(JuryError::JuryClosed(index))
+}
+
+// Add the juries selected by the first version to JURIES, closed as it could
+// not close them, and their blocks to SELECTIONS.  Its Expand blocks extend the
+// Select block with their rand.
+fn migrate_juries() {
+    let mut selects: BTreeMap<Blob, u32> = BTreeMap::new();
+    for index in 0..length() {
+        let data = read_data(index);
+        let Some(rand) = data.rand else {
+            continue;
+        };
+        match data.kind {
+            Kind::Select => {
+                selects.insert(rand, index);
+                add_to_jury(index, index);
+                add_selection(&data.pool, index);
             }
-            if !found {
-                let spans = history.as_slice().chunks_exact(2);
-                for span in spans.clone() {
-                    // check a span of history where the juror was active
-                    if span[0] <= index && span[1] > index {
-                        pool.push(k.to_vec());
-                        break;
-                    }
+            Kind::Expand => {
+                if let Some(select) = selects.get(&rand) {
+                    add_to_jury(*select, index);
                 }
-                // no need to check the remainder as this was covered above
+                add_selection(&data.pool, index);
             }
-        });
-        pool
-    })
+            _ => (),
+        }
+    }
 }
 
-fn make_jury(index: u32, count: u32, seed: Hash) -> Vec<Blob> {
-    let mut rng = make_rng(seed);
-    let pool = collect_pool(index);
-    let mut result = Vec::new();
-    let sample: Vec<usize> = sample(&mut rng, count as usize, pool.len());
-    for s in sample {
-        let juror: &Blob = pool.get(s).unwrap();
-        result.push(juror.clone());
-    }
-    result
+// The log indexes of the Select blocks of the open juries of the pool.
+fn open_juries(pool: &Option<String>) -> Vec<u32> {
+    let pool = PoolStorable(pool.clone().unwrap_or_default());
+    let start = (pool.clone(), 0);
+    let end = (pool, u32::MAX);
+    OPEN_JURIES.with(|o| o.borrow().range(start..=end).map(|((_, s), _)| s).collect())
+}
+
+// The jurors on the panels of the open juries of the pool and their alternates
+// not yet seated, in the order they were drawn, each once.
+fn serving(pool: &Option<String>) -> Vec<Blob> {
+    selection::serving(open_juries(pool).into_iter().map(jury_blocks))
 }
 
-#[ic_cdk_macros::update(guard = "is_authorized")]
+#[ic_cdk_macros::query]
 #[candid::candid_method]
-async fn select(index: u32, count: u32, memo: Blob) -> u32 {
-    let mut new_data = Data::default();
-    new_data.kind = Kind::Select;
-    let seed = get_rng_seed().await;
-    new_data.rand = Some(seed.to_vec());
-    new_data.jurors = make_jury(index, count, seed);
-    new_data.memo = memo;
-    push_pending(&new_data);
-    set_certificate();
-    length() - 1
+fn get_pool(
+    index: u32,
+    start: u32,
+    length: u32,
+    pool: Option<String>,
+) -> Result<Vec<Blob>, JuryError> {
+    check_index(index)?;
+    let pool = pool_id(pool)?.unwrap_or_default();
+    Ok(with_pool(&pool, |p| p.range(index, start, length)))
 }
 
-#[ic_cdk_macros::update(guard = "is_authorized")]
+#[ic_cdk_macros::query]
 #[candid::candid_method]
-fn expand(index: u32, count: u32, memo: Blob) -> u32 {
-    let mut new_data = Data::default();
-    new_data.kind = Kind::Expand;
-    let old = get_block(index);
-    new_data.rand = old.data.rand.clone();
-    let seed: Hash = old.data.rand.unwrap().try_into().unwrap();
-    let old_count = old.data.jurors.len() as u32;
-    new_data.jurors = make_jury(index, old_count + count, seed)[old_count as usize..].to_vec();
-    new_data.memo = memo;
-    push_pending(&new_data);
-    set_certificate();
-    length() - 1
+fn get_history(juror: Blob, pool: Option<String>) -> Vec<u32> {
+    juror_history(&pool.unwrap_or_default(), &juror)
 }
 
+// The ids of the pools jurors have been added to, "" being the default pool.
 #[ic_cdk_macros::query]
 #[candid::candid_method]
-fn get_pool(index: u32, start: u32, length: u32) -> Vec<Blob> {
-    let pool = collect_pool(index);
-    return pool[(start as usize)..((start + length) as usize)].to_vec();
+fn get_pools() -> Vec<String> {
+    POOLS.with(|p| p.borrow().keys().cloned().collect())
+}
+
+// Set the cooldown recorded in and applied by later selections from the pool,
+// None for no cooldown.
+#[ic_cdk_macros::update]
+#[candid::candid_method]
+fn set_cooldown(cooldown: Option<Cooldown>, pool: Option<String>) -> Result<(), JuryError> {
+    is_authorized(Role::PoolManager)?;
+    let pool = PoolStorable(pool_id(pool)?.unwrap_or_default());
+    COOLDOWNS.with(|c| match cooldown {
+        Some(cooldown) => c.borrow_mut().insert(pool, CooldownStorable(cooldown)),
+        None => c.borrow_mut().remove(&pool),
+    });
+    Ok(())
 }
 
 #[ic_cdk_macros::query]
 #[candid::candid_method]
-fn get_history(juror: Blob) -> Vec<u32> {
-    TREE.with(|t| {
-        if let Some(h) = t.borrow().get(juror.as_slice()) {
-            to_history(h)
-        } else {
-            Vec::new()
-        }
-    })
+fn get_cooldown(pool: Option<String>) -> Result<Option<Cooldown>, JuryError> {
+    Ok(pool_cooldown(&pool_id(pool)?.unwrap_or_default()))
 }
 
 #[ic_cdk_macros::query]
@@ -329,44 +1446,73 @@ fn get_certificate() -> Option<Blob> {
     if PENDING_DATA.with(|d| d.borrow().get().0.len()) == 0 {
         None
     } else {
-        ic_cdk::api::data_certificate()
+        api::data_certificate()
     }
 }
 
-fn sample<R, IndexVec>(rng: &mut R, amount: usize, length: usize) -> IndexVec
-where
-    R: Rng + ?Sized,
-    IndexVec: From<Vec<usize>>,
-{
-    assert!(amount <= length);
-    let mut cache = HashSet::with_capacity(amount as usize);
-    let distr = Uniform::new(0, length);
-    let mut indices = Vec::with_capacity(amount as usize);
-    for _ in 0..amount {
-        let mut pos = distr.sample(rng);
-        while !cache.insert(pos) {
-            pos = distr.sample(rng);
-        }
-        indices.push(pos);
-    }
-    IndexVec::from(indices)
+fn get_previous_hash() -> Hash {
+    PREVIOUS_HASH.with(|h| h.borrow().get().0)
 }
 
-fn get_previous_hash() -> Hash {
-    let mut previous_hash = PREVIOUS_HASH.with(|h| h.borrow().get().0.clone());
-    LOG.with(|l| {
-        let l = l.borrow();
-        if l.len() > 0 {
-            previous_hash =
-                sha2::Sha256::digest(Encode!(&l.get(l.len() - 1).unwrap()).unwrap()).into();
+// The hash of a block as committed, recorded as the previous_hash of the next
+// commit.  The encoding of the block depends on the fields of Data at the time,
+// so the hash is stored rather than recomputed from the decoded block.
+fn block_hash(encoded_block: &Blob) -> Hash {
+    sha2::Sha256::digest(Encode!(encoded_block).unwrap()).into()
+}
+
+fn set_previous_hash(hash: Hash) {
+    PREVIOUS_HASH.with(|h| h.borrow_mut().set(StoreHash(hash)).unwrap());
+}
+
+// Number of committed blocks.
+fn committed() -> u32 {
+    BLOCKS.with(|b| b.borrow().len()) as u32
+}
+
+// Move blocks from LOG to BLOCKS, storing the certificate and tree once per commit.
+fn migrate_log() {
+    let legacy = LOG.with(|l| l.borrow().len());
+    if legacy == 0 {
+        return;
+    }
+    let mut last: Option<StoreCommit> = None;
+    for i in 0..legacy {
+        let block: Block = LOG.with(|l| candid::decode_one(&l.borrow().get(i).unwrap()).unwrap());
+        let same_commit = last
+            .as_ref()
+            .is_some_and(|c| c.certificate == block.certificate && c.tree == block.tree);
+        if !same_commit {
+            let commit = StoreCommit {
+                certificate: block.certificate,
+                tree: block.tree,
+                previous_hash: block.previous_hash,
+            };
+            COMMITS.with(|c| c.borrow().append(&Encode!(&commit).unwrap()).unwrap());
+            last = Some(commit);
         }
+        let block = StoreBlock {
+            commit: COMMITS.with(|c| c.borrow().len()) as u32 - 1,
+            data: block.data,
+        };
+        BLOCKS.with(|b| b.borrow().append(&Encode!(&block).unwrap()).unwrap());
+    }
+    set_previous_hash(block_hash(
+        &LOG.with(|l| l.borrow().get(legacy - 1).unwrap()),
+    ));
+    LOG.with(|l| {
+        *l.borrow_mut() = Log::new(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
+        )
     });
-    previous_hash
 }
 
-fn build_tree(data: &Vec<Data>, previous_hash: &Hash) -> BlockTree {
+fn build_tree(data: &[Data], previous_hash: &Hash) -> BlockTree {
     let mut tree = BlockTree::default();
-    let offset = length() as u32;
+    // Label the pending blocks by their log index.  The first version added the
+    // number of pending blocks to it.
+    let offset = committed();
     for (i, d) in data.iter().enumerate() {
         let hash: [u8; 32] = sha2::Sha256::digest(Encode!(d).unwrap()).into();
         let i = (i as u32) + offset;
@@ -376,68 +1522,80 @@ fn build_tree(data: &Vec<Data>, previous_hash: &Hash) -> BlockTree {
     tree
 }
 
-#[ic_cdk_macros::update(guard = "is_authorized")]
+#[ic_cdk_macros::update]
 #[candid::candid_method]
-fn commit(certificate: Blob) -> Option<u32> {
+fn commit(certificate: Blob) -> Result<Option<u32>, JuryError> {
+    is_authorized(Role::Committer)?;
     let data = PENDING_DATA.with(|d| d.borrow().get().0.clone());
-    if data.len() == 0 {
-        return None;
+    if data.is_empty() {
+        return Ok(None);
     }
     let previous_hash = get_previous_hash();
-    // Check that the certificate corresponds to our tree.  Note: we are
-    // not fully verifying the certificate, just checking for races.
+    // Check that the certificate is signed by the root key, recent and
+    // certifies our tree.
     let tree = build_tree(&data, &previous_hash);
     let certified_data = &ic_certified_map::labeled_hash(b"jury_blocks", &tree.root_hash());
-    let cert: ReplicaCertificate = serde_cbor::from_slice(&certificate[..]).unwrap();
-    let canister_id = ic_cdk::api::id();
+    let cert = Certificate::from_cbor(&certificate).map_err(JuryError::MalformedCertificate)?;
+    let canister_id = api::id();
     let canister_id = canister_id.as_slice();
-    if let LookupResult::Found(certified_data_bytes) = cert.tree.lookup_path(&[
-        "canister".into(),
-        canister_id.into(),
-        "certified_data".into(),
-    ]) {
-        assert!(certified_data == certified_data_bytes);
-    } else {
-        ic_cdk::trap("certificate mismatch");
+    cert.verify(&root_key(), canister_id)
+        .map_err(JuryError::InvalidCertificate)?;
+    let time = cert
+        .time()
+        .ok_or_else(|| JuryError::MalformedCertificate("missing time".to_string()))?;
+    if time.saturating_add(MAX_CERTIFICATE_AGE) < api::time() {
+        return Err(JuryError::CertificateTooOld(time));
     }
-    LOG.with(|l| {
-        let l = l.borrow_mut();
-        let hash_tree = ic_certified_map::labeled(b"jury_blocks", tree.as_hash_tree());
-        let mut serializer = serde_cbor::ser::Serializer::new(vec![]);
-        serializer.self_describe().unwrap();
-        hash_tree.serialize(&mut serializer).unwrap();
-        let tree: Blob = serializer.into_inner();
-        for d in data {
-            let block = Block {
-                certificate: certificate.clone(),
-                tree: tree.clone(),
-                data: d,
-                previous_hash,
-            };
-            let encoded_block = Encode!(&block).unwrap();
-            l.append(&encoded_block).unwrap();
+    if cert.certified_data(canister_id).as_deref() != Some(&certified_data[..]) {
+        return Err(JuryError::CertificateMismatch);
+    }
+    let hash_tree = ic_certified_map::labeled(b"jury_blocks", tree.as_hash_tree());
+    let mut serializer = serde_cbor::ser::Serializer::new(vec![]);
+    serializer.self_describe().unwrap();
+    hash_tree.serialize(&mut serializer).unwrap();
+    append_commit(certificate, serializer.into_inner(), previous_hash);
+    Ok(Some(length()))
+}
+
+// Move the pending blocks to BLOCKS as a commit with the certificate and tree.
+fn append_commit(certificate: Blob, tree: Blob, previous_hash: Hash) {
+    let data = PENDING_DATA.with(|d| d.borrow().get().0.clone());
+    let commit = StoreCommit {
+        certificate,
+        tree,
+        previous_hash,
+    };
+    let commit = COMMITS.with(|c| c.borrow().append(&Encode!(&commit).unwrap()).unwrap()) as u32;
+    BLOCKS.with(|b| {
+        let b = b.borrow();
+        for data in data {
+            let block = StoreBlock { commit, data };
+            b.append(&Encode!(&block).unwrap()).unwrap();
         }
     });
     PENDING_DATA.with(|d| d.borrow_mut().set(StoreData::default()).unwrap());
-    Some(length())
+    let last = Encode!(&read_block(committed() - 1)).unwrap();
+    set_previous_hash(block_hash(&last));
 }
 
 #[ic_cdk_macros::query]
 #[candid::candid_method]
-fn get_size(index: u32) -> u32 {
-    get_block(index).data.jurors.len() as u32
+fn get_size(index: u32) -> Result<u32, JuryError> {
+    Ok(get_data(index)?.jurors.len() as u32)
 }
 
 #[ic_cdk_macros::query]
 #[candid::candid_method]
-fn get_pool_size(index: u32) -> u32 {
-    collect_pool(index).len() as u32
+fn get_pool_size(index: u32, pool: Option<String>) -> Result<u32, JuryError> {
+    check_index(index)?;
+    let pool = pool_id(pool)?.unwrap_or_default();
+    Ok(with_pool(&pool, |p| p.size(index)))
 }
 
 #[ic_cdk_macros::query]
 #[candid::candid_method]
 fn length() -> u32 {
-    (LOG.with(|l| l.borrow().len()) + PENDING_DATA.with(|d| d.borrow().get().0.len() as u64)) as u32
+    committed() + PENDING_DATA.with(|d| d.borrow().get().0.len() as u32)
 }
 
 #[ic_cdk_macros::query]
@@ -448,135 +1606,284 @@ fn get_pending() -> u32 {
 
 #[ic_cdk_macros::query]
 #[candid::candid_method]
-fn get_block(index: u32) -> Block {
-    LOG.with(|l| {
-        let committed = l.borrow().len() as u32;
-        if index < committed {
-            return candid::decode_one(&l.borrow().get(index as u64).unwrap()).unwrap();
-        }
-        let mut b = Block::default();
-        b.data = PENDING_DATA.with(|d| {
-            d.borrow()
-                .get()
-                .0
-                .get((index - committed) as usize)
-                .unwrap()
-                .clone()
-        });
-        b
+fn get_block(index: u32) -> Result<Block, JuryError> {
+    check_index(index)?;
+    Ok(read_block(index))
+}
+
+// The block at index, which must be less than length().
+fn read_block(index: u32) -> Block {
+    let committed = committed();
+    if index < committed {
+        let block: StoreBlock =
+            BLOCKS.with(|b| candid::decode_one(&b.borrow().get(index as u64).unwrap()).unwrap());
+        let commit: StoreCommit = COMMITS
+            .with(|c| candid::decode_one(&c.borrow().get(block.commit as u64).unwrap()).unwrap());
+        return Block {
+            certificate: commit.certificate,
+            tree: commit.tree,
+            data: block.data,
+            previous_hash: commit.previous_hash,
+        };
+    }
+    Block {
+        data: read_data(index),
+        ..Default::default()
+    }
+}
+
+// The data of the block at index, which must be less than length(), without
+// decoding its commit.
+fn read_data(index: u32) -> Data {
+    let committed = committed();
+    if index < committed {
+        let block: StoreBlock =
+            BLOCKS.with(|b| candid::decode_one(&b.borrow().get(index as u64).unwrap()).unwrap());
+        return block.data;
+    }
+    PENDING_DATA.with(|d| {
+        d.borrow()
+            .get()
+            .0
+            .get((index - committed) as usize)
+            .unwrap()
+            .clone()
     })
 }
 
+// The data of the block at index.
+fn get_data(index: u32) -> Result<Data, JuryError> {
+    check_index(index)?;
+    Ok(read_data(index))
+}
+
+// Committed blocks only, as pending blocks have no certificate or tree yet.
 #[ic_cdk_macros::query]
 #[candid::candid_method]
-fn get_jurors(index: u32) -> Vec<Blob> {
-    get_block(index).data.jurors
+fn get_blocks(start: u32, length: u32) -> Blocks {
+    let committed = committed();
+    let end = start.saturating_add(length).min(committed);
+    let mut page = Blocks::default();
+    let mut size = 0;
+    let mut index = start;
+    while index < end {
+        let block = read_block(index);
+        size += Encode!(&block).unwrap().len();
+        // Always return at least one block so that the caller makes progress.
+        if size > MAX_BLOCKS_SIZE && !page.blocks.is_empty() {
+            break;
+        }
+        page.blocks.push(block);
+        index += 1;
+    }
+    if index < committed && length > 0 {
+        page.next = Some(index);
+    }
+    page
 }
 
 #[ic_cdk_macros::query]
 #[candid::candid_method]
-fn find(index: u32, jurors: Vec<Blob>) -> Vec<Option<u32>> {
-    let pool = collect_pool(index);
-    let mut m: HashMap<Blob, u32> = HashMap::new();
-    for (i, j) in jurors.iter().enumerate() {
-        m.insert(j.clone(), i as u32);
-    }
-    let mut result: Vec<Option<u32>> = Vec::new();
-    result.resize(jurors.len(), None);
-    for (i, j) in pool.iter().enumerate() {
-        if let Some(k) = m.get(j) {
-            result[*k as usize] = Some(i as u32);
-        }
+fn get_jurors(index: u32) -> Result<Vec<Blob>, JuryError> {
+    Ok(get_data(index)?.jurors)
+}
+
+#[ic_cdk_macros::query]
+#[candid::candid_method]
+fn find(
+    index: u32,
+    jurors: Vec<Blob>,
+    pool: Option<String>,
+) -> Result<Vec<Option<u32>>, JuryError> {
+    check_index(index)?;
+    let pool = pool_id(pool)?.unwrap_or_default();
+    Ok(with_pool(&pool, |p| {
+        jurors.iter().map(|j| p.rank(index, j)).collect()
+    }))
+}
+
+// The blinded ids of a Select, Expand or Replace block, none for blocks before
+// they were recorded.
+fn jury_blinded(index: u32) -> Result<Vec<Blob>, JuryError> {
+    let data = get_data(index)?;
+    match data.kind {
+        Kind::Select | Kind::Expand | Kind::Replace => Ok(data.blinded.unwrap_or_default()),
+        _ => Err(JuryError::NotASelection(index)),
     }
-    result
+}
+
+// Whether the juror with the per-jury token sha256(secret || rand) was drawn
+// by a Select, Expand or Replace block, so revealing the token proves
+// membership without the pool id or the secret.
+#[ic_cdk_macros::query]
+#[candid::candid_method]
+fn check_token(index: u32, token: Blob) -> Result<bool, JuryError> {
+    let blinded = jury_blinded(index)?;
+    Ok(selection::find_token(&blinded, &token))
 }
 
 #[ic_cdk_macros::query]
 #[candid::candid_method]
-fn get_authorized() -> Vec<Principal> {
+fn get_authorized() -> Vec<Authorization> {
     let mut authorized = Vec::new();
     AUTH.with(|a| {
-        for (k, _v) in a.borrow().iter() {
-            authorized.push(k.0);
+        for (k, v) in a.borrow().iter() {
+            authorized.push(Authorization {
+                id: k.0,
+                roles: roles_from_bits(v),
+            });
         }
     });
     authorized
 }
 
-#[ic_cdk_macros::update(guard = "is_authorized")]
+fn roles_from_bits(bits: u32) -> Vec<Role> {
+    (0..u32::BITS)
+        .filter(|i| bits & (1 << i) != 0)
+        .filter_map(Role::from_u32)
+        .collect()
+}
+
+fn roles_to_bits(roles: &[Role]) -> u32 {
+    roles.iter().fold(0, |bits, r| bits | 1 << *r as u32)
+}
+
+// Stage an Authorize block giving the principal the roles, or all roles if
+// None.  No roles removes the principal.
+#[ic_cdk_macros::update]
 #[candid::candid_method]
-fn authorize(principal: Principal) {
-    let value = Auth::Admin;
+fn authorize(principal: Principal, roles: Option<Vec<Role>>) -> Result<u32, JuryError> {
+    is_authorized(Role::AuthAdmin)?;
+    let bits = roles.map_or(ALL_ROLES, |r| roles_to_bits(&r));
+    let new_data = Data {
+        kind: Kind::Authorize,
+        principal: Some(principal),
+        roles: Some(roles_from_bits(bits)),
+        ..Default::default()
+    };
+    push_pending(&new_data);
     AUTH.with(|a| {
-        a.borrow_mut()
-            .insert(PrincipalStorable(principal), value as u32);
+        let mut a = a.borrow_mut();
+        if bits == 0 {
+            a.remove(&PrincipalStorable(principal));
+        } else {
+            a.insert(PrincipalStorable(principal), bits);
+        }
     });
+    set_certificate();
+    Ok(length() - 1)
 }
 
-#[ic_cdk_macros::update(guard = "is_authorized")]
+// Stage a Deauthorize block removing the principal.
+#[ic_cdk_macros::update]
 #[candid::candid_method]
-fn deauthorize(principal: Principal) {
-    AUTH.with(|a| {
-        a.borrow_mut()
-            .remove(&PrincipalStorable(principal))
-            .unwrap();
-    });
+fn deauthorize(principal: Principal) -> Result<u32, JuryError> {
+    is_authorized(Role::AuthAdmin)?;
+    AUTH.with(|a| a.borrow_mut().remove(&PrincipalStorable(principal)))
+        .ok_or(JuryError::UnknownPrincipal(principal))?;
+    let new_data = Data {
+        kind: Kind::Deauthorize,
+        principal: Some(principal),
+        ..Default::default()
+    };
+    push_pending(&new_data);
+    set_certificate();
+    Ok(length() - 1)
 }
 
-fn is_authorized() -> Result<(), String> {
-    if ic_cdk::api::is_controller(&ic_cdk::caller()) {
+// Controllers have all roles.
+fn is_authorized(role: Role) -> Result<(), JuryError> {
+    if api::is_controller(&api::caller()) {
         return Ok(());
     }
-    AUTH.with(|a| {
-        if a.borrow()
-            .contains_key(&PrincipalStorable(ic_cdk::caller()))
-        {
-            Ok(())
-        } else {
-            Err("You are not authorized".to_string())
-        }
-    })
+    AUTH.with(
+        |a| match a.borrow().get(&PrincipalStorable(api::caller())) {
+            Some(bits) if bits & (1 << role as u32) != 0 => Ok(()),
+            _ => Err(JuryError::Unauthorized),
+        },
+    )
 }
 
-async fn get_rng_seed() -> Hash {
-    let raw_rand: Vec<u8> =
-        match ic_cdk::call(Principal::management_canister(), "raw_rand", ()).await {
-            Ok((res,)) => res,
-            Err((_, err)) => ic_cdk::trap(&format!("failed to get seed: {}", err)),
-        };
-    let seed: Hash = raw_rand[..].try_into().unwrap_or_else(|_| {
-        ic_cdk::trap(&format!(
-                "when creating seed from raw_rand output, expected raw randomness to be of length 32, got {}",
-                raw_rand.len()
-                ));
+// Give principals with the Admin role of the first version all roles, staging
+// an Authorize block for each so that the chain records them.
+fn migrate_auth() {
+    let legacy: Vec<PrincipalStorable> = AUTH.with(|a| {
+        a.borrow()
+            .iter()
+            .filter(|(_, v)| *v == LEGACY_ADMIN)
+            .map(|(k, _)| k)
+            .collect()
     });
-    seed
+    for k in legacy {
+        push_pending(&Data {
+            kind: Kind::Authorize,
+            principal: Some(k.0),
+            roles: Some(roles_from_bits(ALL_ROLES)),
+            ..Default::default()
+        });
+        AUTH.with(|a| a.borrow_mut().insert(k, ALL_ROLES));
+    }
 }
 
-fn make_rng(seed: Hash) -> rand_chacha::ChaCha20Rng {
-    rand_chacha::ChaCha20Rng::from_seed(seed)
+async fn get_rng_seed() -> Result<Hash, JuryError> {
+    let raw_rand = api::raw_rand()
+        .await
+        .map_err(|err| JuryError::Randomness(format!("failed to get seed: {}", err)))?;
+    raw_rand[..].try_into().map_err(|_| {
+        JuryError::Randomness(format!(
+            "expected raw randomness to be of length 32, got {}",
+            raw_rand.len()
+        ))
+    })
 }
 
 #[ic_cdk_macros::init]
 #[candid::candid_method]
-fn canister_init(previous_hash: Option<String>) {
+fn canister_init(previous_hash: Option<String>, root_key: Option<String>) {
+    if let Some(root_key) = root_key {
+        // For local replicas, which have their own root key.
+        let root_key =
+            hex::decode(root_key).unwrap_or_else(|_| ic_cdk::trap("root_key must be hex"));
+        ROOT_KEY.with(|k| k.borrow_mut().set(StoreKey(root_key)).unwrap());
+    }
     if let Some(previous_hash) = previous_hash {
         if let Ok(previous_hash) = hex::decode(&previous_hash) {
             if previous_hash.len() == 32 {
-                PREVIOUS_HASH.with(|h| {
-                    let hash: Hash = previous_hash.try_into().unwrap();
-                    h.borrow_mut().set(StoreHash(hash)).unwrap();
-                });
-                return;
+                set_previous_hash(previous_hash.try_into().unwrap());
             }
         }
     }
 }
 
+// The root key certificates must be signed with.
+fn root_key() -> Blob {
+    let root_key = ROOT_KEY.with(|k| k.borrow().get().0.clone());
+    if root_key.is_empty() {
+        hex::decode(IC_ROOT_KEY).unwrap()
+    } else {
+        root_key
+    }
+}
+
 #[ic_cdk_macros::post_upgrade]
 #[candid::candid_method]
 fn post_upgrade() {
-    // Reload state.
+    migrate_log();
+    migrate_auth();
+    // Reload state, rebuilding POOL_HISTORY by replaying the log if it is empty.
+    if POOL_HISTORY.with(|h| h.borrow().is_empty()) && length() > 0 {
+        rebuild_pool();
+    } else {
+        load_pool();
+    }
+    // JURIES is empty after the upgrade from the first version.
+    if JURIES.with(|j| j.borrow().is_empty()) && length() > 0 {
+        migrate_juries();
+    }
+    // Certified data does not survive the upgrade, so recertify any pending blocks.
+    if get_pending() > 0 {
+        set_certificate();
+    }
 }
 
 candid::export_service!();
@@ -593,3 +1900,844 @@ fn main() {
 
 #[cfg(target_arch = "wasm32")]
 fn main() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certificate::tests::{certificate, delegation, public_key};
+    use crate::hash_tree::{fork, label, leaf};
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+
+    // The Kind, Data and Block of the first version, which stored the blocks in
+    // LOG with the certificate and tree of their commit.
+    #[derive(CandidType)]
+    enum Kind0 {
+        Add,
+        Remove,
+        Select,
+        Expand,
+    }
+
+    #[derive(CandidType)]
+    struct Data0 {
+        kind: Kind0,
+        jurors: Vec<Blob>,
+        rand: Option<Blob>,
+        memo: Blob,
+    }
+
+    #[derive(CandidType)]
+    struct Block0 {
+        certificate: Blob,
+        tree: Blob,
+        data: Data0,
+        previous_hash: Hash,
+    }
+
+    fn data0(kind: Kind0, jurors: &[u8], rand: Option<u8>) -> Data0 {
+        Data0 {
+            kind,
+            jurors: jurors.iter().map(|j| vec![*j]).collect(),
+            rand: rand.map(|r| vec![r; 32]),
+            memo: b"memo".to_vec(),
+        }
+    }
+
+    // Append the blocks of the first version to LOG in commits of two blocks and
+    // return their encodings.
+    fn seed_log(data: Vec<Data0>) -> Vec<Blob> {
+        let mut log: Vec<Blob> = Vec::new();
+        let mut previous_hash = [0; 32];
+        for (i, data) in data.into_iter().enumerate() {
+            if i % 2 == 0 && i > 0 {
+                previous_hash = block_hash(log.last().unwrap());
+            }
+            let block = Block0 {
+                certificate: vec![(i / 2) as u8],
+                tree: vec![(i / 2) as u8 + 10],
+                data,
+                previous_hash,
+            };
+            let encoded = Encode!(&block).unwrap();
+            LOG.with(|l| l.borrow().append(&encoded).unwrap());
+            log.push(encoded);
+        }
+        log
+    }
+
+    #[test]
+    fn log_migration() {
+        let log = seed_log(vec![
+            data0(Kind0::Add, &[0, 1, 2, 3], None),
+            data0(Kind0::Select, &[2], Some(7)),
+            data0(Kind0::Expand, &[0], Some(7)),
+            data0(Kind0::Remove, &[1], None),
+        ]);
+        migrate_log();
+        assert_eq!(LOG.with(|l| l.borrow().len()), 0);
+        assert_eq!(committed(), 4);
+        assert_eq!(COMMITS.with(|c| c.borrow().len()), 2);
+        for (index, encoded) in log.iter().enumerate() {
+            // The fields added since the first version are None.
+            let expected = Decode!(encoded, Block).unwrap();
+            let block = read_block(index as u32);
+            assert_eq!(Encode!(&block).unwrap(), Encode!(&expected).unwrap());
+            assert_eq!(block.certificate, [(index / 2) as u8]);
+        }
+        assert_eq!(read_block(2).previous_hash, block_hash(&log[1]));
+        // The next commit links to the last block as the first version stored
+        // it, not as it is encoded now.
+        assert_eq!(get_previous_hash(), block_hash(&log[3]));
+        let reencoded = Encode!(&read_block(3)).unwrap();
+        assert_ne!(get_previous_hash(), block_hash(&reencoded));
+    }
+
+    #[test]
+    fn previous_hash() {
+        set_previous_hash([1; 32]);
+        push_pending(&Data::default());
+        append_commit(vec![], vec![], get_previous_hash());
+        assert_eq!(read_block(0).previous_hash, [1; 32]);
+        let hash = block_hash(&Encode!(&read_block(0)).unwrap());
+        assert_eq!(get_previous_hash(), hash);
+        push_pending(&Data::default());
+        push_pending(&Data::default());
+        append_commit(vec![], vec![], get_previous_hash());
+        assert_eq!(read_block(2).previous_hash, hash);
+        assert_eq!(
+            get_previous_hash(),
+            block_hash(&Encode!(&read_block(2)).unwrap())
+        );
+    }
+
+    #[test]
+    fn auth_migration() {
+        let principal = |i: u8| PrincipalStorable(Principal::from_slice(&[i]));
+        AUTH.with(|a| {
+            let mut a = a.borrow_mut();
+            a.insert(principal(1), LEGACY_ADMIN);
+            a.insert(principal(2), 1 << Role::Selector as u32);
+            a.insert(principal(3), LEGACY_ADMIN);
+        });
+        migrate_auth();
+        let roles = |i| AUTH.with(|a| a.borrow().get(&principal(i)));
+        assert_eq!(roles(1), Some(ALL_ROLES));
+        assert_eq!(roles(2), Some(1 << Role::Selector as u32));
+        assert_eq!(roles(3), Some(ALL_ROLES));
+        // The chain records the roles given by the migration.
+        assert_eq!(get_pending(), 2);
+        for (block, i) in [(0, 1), (1, 3)] {
+            let data = &PENDING_DATA.with(|d| d.borrow().get().0.clone())[block];
+            assert!(matches!(data.kind, Kind::Authorize));
+            assert_eq!(data.principal, Some(principal(i).0));
+            assert_eq!(data.roles, Some(roles_from_bits(ALL_ROLES)));
+        }
+        migrate_auth();
+        assert_eq!(get_pending(), 2);
+    }
+
+    // Add and Remove blocks over two pools with weights and categories.
+    fn pool_changes() -> Vec<Data> {
+        let change = |kind, pool: Option<&str>, jurors: &[u8]| Data {
+            kind,
+            jurors: jurors.iter().map(|j| vec![*j]).collect(),
+            pool: pool.map(str::to_string),
+            ..Default::default()
+        };
+        let categories = |c: &[&str]| Some(c.iter().map(|c| c.to_string()).collect());
+        vec![
+            Data {
+                weights: Some(vec![1, 2, 3, 0, 5, 1]),
+                categories: categories(&["x", "x", "", "x", "", ""]),
+                ..change(Kind::Add, None, &[1, 2, 3, 4, 5, 6])
+            },
+            change(Kind::Add, Some("b"), &[1, 2, 3]),
+            change(Kind::Remove, None, &[2, 4]),
+            Data {
+                weights: Some(vec![4, 1]),
+                categories: categories(&["x", "x"]),
+                ..change(Kind::Add, None, &[2, 7])
+            },
+            change(Kind::Remove, Some("b"), &[1]),
+            change(Kind::Add, None, &[4]),
+        ]
+    }
+
+    // Stage the changes in commits of two blocks, leaving the last two pending,
+    // applying them to the pool if apply.
+    fn stage_pool(changes: Vec<Data>, apply: bool) {
+        for (i, data) in changes.into_iter().enumerate() {
+            if i % 2 == 0 && i > 0 && i < 5 {
+                append_commit(vec![], vec![], get_previous_hash());
+            }
+            push_pending(&data);
+            if apply {
+                apply_to_pool(length() - 1, data);
+            }
+        }
+    }
+
+    // The jurors of each pool and those drawn from it at each index.
+    fn draws() -> Vec<String> {
+        let quotas = [("x", 1), ("", 1)].map(|(category, count)| Quota {
+            category: category.to_string(),
+            count,
+        });
+        let mut draws = Vec::new();
+        for pool in ["", "b"] {
+            for index in 0..length() {
+                let seed = [index as u8; 32];
+                draws.push(format!(
+                    "{:?} {:?} {:?} {:?}",
+                    with_pool(pool, |p| p.range(index, 0, p.size(index))),
+                    make_jury(pool, index, 2, seed, Some(2), &[]),
+                    make_jury(
+                        pool,
+                        index,
+                        2,
+                        seed,
+                        Some(selection::WEIGHTED_ALGORITHM),
+                        &[]
+                    ),
+                    with_strata(pool, &quotas, |s| {
+                        selection::make_stratified_jury(s, index, seed, &quotas, &[])
+                    }),
+                ));
+            }
+        }
+        draws
+    }
+
+    #[test]
+    fn rebuilt_pool() {
+        let expected = std::thread::spawn(|| {
+            stage_pool(pool_changes(), true);
+            draws()
+        })
+        .join()
+        .unwrap();
+        stage_pool(pool_changes(), false);
+        rebuild_pool();
+        assert_eq!(draws(), expected);
+    }
+
+    #[test]
+    fn loaded_pool() {
+        stage_pool(pool_changes(), true);
+        let expected = draws();
+        POOLS.with(|p| p.borrow_mut().clear());
+        STRATA.with(|s| s.borrow_mut().clear());
+        load_pool();
+        assert_eq!(draws(), expected);
+    }
+
+    // Commit a block with a memo of size bytes.
+    fn commit_memo(size: usize) {
+        push_pending(&Data {
+            memo: vec![0; size],
+            ..Default::default()
+        });
+        append_commit(vec![], vec![], get_previous_hash());
+    }
+
+    #[test]
+    fn pages() {
+        for _ in 0..5 {
+            commit_memo(1);
+        }
+        push_pending(&Data::default());
+        let page = get_blocks(1, 3);
+        assert_eq!(page.blocks.len(), 3);
+        assert_eq!(page.next, Some(4));
+        // Pending blocks are not returned.
+        let page = get_blocks(4, 10);
+        assert_eq!(page.blocks.len(), 1);
+        assert_eq!(page.next, None);
+        assert_eq!(get_blocks(5, 10).blocks.len(), 0);
+        assert_eq!(get_blocks(u32::MAX, u32::MAX).next, None);
+        // A pager asking for no blocks is not sent back to the same start.
+        let page = get_blocks(1, 0);
+        assert_eq!(page.blocks.len(), 0);
+        assert_eq!(page.next, None);
+    }
+
+    #[test]
+    fn page_size() {
+        let third = MAX_BLOCKS_SIZE / 3;
+        for _ in 0..4 {
+            commit_memo(third);
+        }
+        commit_memo(MAX_BLOCKS_SIZE);
+        commit_memo(1);
+        // Three blocks of a third of the bound with their encoding exceed it.
+        let page = get_blocks(0, 10);
+        assert_eq!(page.blocks.len(), 2);
+        assert_eq!(page.next, Some(2));
+        let page = get_blocks(2, 10);
+        assert_eq!(page.blocks.len(), 2);
+        assert_eq!(page.next, Some(4));
+        // A block larger than the bound is returned alone.
+        let page = get_blocks(4, 10);
+        assert_eq!(page.blocks.len(), 1);
+        assert_eq!(page.next, Some(5));
+        let size: usize = page.blocks.iter().map(|b| Encode!(b).unwrap().len()).sum();
+        assert!(size > MAX_BLOCKS_SIZE);
+    }
+
+    // The secrets of the test root key and of the subnet key delegated by it.
+    const ROOT_SECRET: u64 = 0x6a75_7279;
+    const SUBNET_SECRET: u64 = 0x7375_626e;
+
+    fn leb128(mut value: u64) -> Blob {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(byte);
+                return bytes;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+
+    // A certificate of the certified data at the current time, signed by the
+    // root key or by the subnet key with a delegation from it.
+    fn certify(certified_data: &[u8], delegated: bool) -> Blob {
+        let tree = fork(
+            label(
+                "canister",
+                label(
+                    api::id().as_slice(),
+                    label("certified_data", leaf(certified_data)),
+                ),
+            ),
+            label("time", leaf(leb128(api::time()))),
+        );
+        if delegated {
+            let ranges = (&[0][..], &[0, 0, 0, 0, 0, 0, 0, 2][..]);
+            let delegation = delegation(ROOT_SECRET, SUBNET_SECRET, ranges.0, ranges.1);
+            certificate(tree, SUBNET_SECRET, Some(delegation))
+        } else {
+            certificate(tree, ROOT_SECRET, None)
+        }
+    }
+
+    fn commit_pending(delegated: bool) {
+        let certificate = certify(&api::certified_data(), delegated);
+        commit(certificate).unwrap().unwrap();
+    }
+
+    // Run an update call which does not wait on other canisters.
+    fn run<F: Future>(call: F) -> F::Output {
+        let mut call = std::pin::pin!(call);
+        let mut context = Context::from_waker(Waker::noop());
+        let Poll::Ready(result) = call.as_mut().poll(&mut context) else {
+            panic!("the call is waiting");
+        };
+        result
+    }
+
+    fn options(set: impl FnOnce(&mut SelectOptions)) -> Option<SelectOptions> {
+        let mut options = SelectOptions::default();
+        set(&mut options);
+        Some(options)
+    }
+
+    // The chain of verifier/fixtures/chain.bin, described in the verifier tests.
+    fn chain() -> Blob {
+        canister_init(None, Some(hex::encode(public_key(ROOT_SECRET))));
+        let party = Principal::from_slice(&[7; 29]);
+        authorize(party, Some(vec![Role::Selector])).unwrap();
+        let jurors: Vec<Blob> = (0..24u8).map(|i| vec![b'j', i]).collect();
+        let secrets = (0..24u8)
+            .map(|i| (i % 3 != 0).then(|| vec![i; 32]))
+            .collect();
+        let weights = (0..24u32).map(|i| i % 4).collect();
+        let categories = (0..24)
+            .map(|i| if i % 2 == 0 { "north" } else { "south" }.to_string())
+            .collect();
+        let memo = |memo: &str| memo.as_bytes().to_vec();
+        add(
+            jurors.clone(),
+            memo("jurors"),
+            None,
+            Some(secrets),
+            Some(weights),
+            Some(categories),
+        )
+        .unwrap();
+        let court = (0..8u8).map(|i| vec![b'c', i]).collect();
+        add(court, memo("court"), Some("court".into()), None, None, None).unwrap();
+        commit_pending(false);
+        set_cooldown(Some(Cooldown::Blocks(2)), None).unwrap();
+        let a = run(select(
+            length() - 1,
+            3,
+            memo("select"),
+            options(|o| {
+                o.strikes = Some(1);
+                o.alternates = Some(2);
+            }),
+        ))
+        .unwrap();
+        let w = run(select_weighted(
+            length() - 1,
+            2,
+            memo("weighted"),
+            options(|o| {
+                o.exclude_serving = Some(true);
+                o.exclude = Some(vec![jurors[3].clone()]);
+            }),
+        ))
+        .unwrap();
+        let quotas = [("north", 2), ("south", 1)]
+            .map(|(category, count)| Quota {
+                category: category.to_string(),
+                count,
+            })
+            .to_vec();
+        let s = run(select_stratified(
+            length() - 1,
+            quotas,
+            memo("stratified"),
+            None,
+        ))
+        .unwrap();
+        let c = run(select(
+            length() - 1,
+            2,
+            memo("court"),
+            options(|o| o.pool = Some("court".into())),
+        ))
+        .unwrap();
+        let e = expand(a, 2, memo("expand")).unwrap();
+        let panel = get_panel(a).unwrap();
+        let r = replace(e, vec![panel[0].clone()], memo("replace")).unwrap();
+        strike(a, vec![panel[1].clone()], party, memo("strike")).unwrap();
+        promote_alternate(r, None, memo("promote")).unwrap();
+        commit_pending(true);
+        close(w, memo("close")).unwrap();
+        close(s, memo("close")).unwrap();
+        remove(
+            vec![jurors[5].clone(), jurors[6].clone()],
+            memo("remove"),
+            None,
+        )
+        .unwrap();
+        let b = run(select(
+            length() - 1,
+            4,
+            memo("after"),
+            options(|o| o.exclude_serving = Some(true)),
+        ))
+        .unwrap();
+        expand(b, 1, memo("expand")).unwrap();
+        expand(b, 1, memo("expand again")).unwrap();
+        close(c, memo("close")).unwrap();
+        commit_pending(true);
+        Encode!(&get_blocks(0, length()).blocks).unwrap()
+    }
+
+    // Commit the blocks to LOG as the first version did: labeled by their log
+    // index plus the number of blocks in the commit, and linked by the hash of
+    // the last block of the previous commit.
+    fn commit0(data: Vec<Data0>, delegated: bool) {
+        let committed = LOG.with(|l| l.borrow().len()) as u32;
+        let previous_hash = match committed {
+            0 => [0; 32],
+            n => block_hash(&LOG.with(|l| l.borrow().get(n as u64 - 1).unwrap())),
+        };
+        let mut tree = BlockTree::default();
+        let offset = committed + data.len() as u32;
+        for (i, d) in data.iter().enumerate() {
+            let hash = sha2::Sha256::digest(Encode!(d).unwrap()).into();
+            tree.insert((offset + i as u32).to_be_bytes().to_vec(), hash);
+        }
+        tree.insert(b"previous_hash".to_vec(), previous_hash);
+        let certified_data = ic_certified_map::labeled_hash(b"jury_blocks", &tree.root_hash());
+        let certificate = certify(&certified_data, delegated);
+        let hash_tree = ic_certified_map::labeled(b"jury_blocks", tree.as_hash_tree());
+        let mut serializer = serde_cbor::ser::Serializer::new(vec![]);
+        serializer.self_describe().unwrap();
+        hash_tree.serialize(&mut serializer).unwrap();
+        let tree = serializer.into_inner();
+        for data in data {
+            let block = Block0 {
+                certificate: certificate.clone(),
+                tree: tree.clone(),
+                data,
+                previous_hash,
+            };
+            LOG.with(|l| l.borrow().append(&Encode!(&block).unwrap()).unwrap());
+        }
+    }
+
+    // The chain of verifier/fixtures/baseline_chain.bin: blocks committed by the
+    // first version, which recorded neither the algorithm (0) nor the pool of a
+    // selection, as the canister exports them after the upgrade.  The first
+    // version drew the jurors of a Select from the pool at an index it did not
+    // record, and of an Expand from the pool at the index of the Select.
+    fn baseline_chain() -> Blob {
+        canister_init(None, Some(hex::encode(public_key(ROOT_SECRET))));
+        let mut pool = Pool::new();
+        let mut draw = |changes: &[(u32, bool, &[u8])], index, count, seed| {
+            for (at, add, jurors) in changes {
+                for j in jurors.iter() {
+                    match add {
+                        true => pool.insert(*at, &[b'j', *j], 1),
+                        false => pool.remove(*at, &[b'j', *j]),
+                    }
+                }
+            }
+            selection::make_jury(&pool, index, count, seed, None, &[]).unwrap()
+        };
+        let data = |kind, jurors: &[u8], rand: Option<Hash>, memo: &str| Data0 {
+            kind,
+            jurors: jurors.iter().map(|j| vec![b'j', *j]).collect(),
+            rand: rand.map(|r| r.to_vec()),
+            memo: memo.as_bytes().to_vec(),
+        };
+        let adds: [&[u8]; 2] = [&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11], &[12, 13, 14, 15]];
+        let first: Hash = run(get_rng_seed()).unwrap();
+        let selected = draw(&[(0, true, adds[0]), (1, true, adds[1])], 1, 3, first);
+        let select = |rand, jurors: &[Blob], memo| Data0 {
+            jurors: jurors.to_vec(),
+            ..data(Kind0::Select, &[], Some(rand), memo)
+        };
+        let expand = |rand, jurors: &[Blob], memo| Data0 {
+            jurors: jurors.to_vec(),
+            ..data(Kind0::Expand, &[], Some(rand), memo)
+        };
+        commit0(
+            vec![
+                data(Kind0::Add, adds[0], None, "jurors"),
+                data(Kind0::Add, adds[1], None, "more jurors"),
+                select(first, &selected, "select"),
+            ],
+            false,
+        );
+        let removed: &[u8] = &[2, 5];
+        let expanded = draw(&[], 2, 5, first)[3..].to_vec();
+        let second: Hash = run(get_rng_seed()).unwrap();
+        let reselected = draw(&[(3, false, removed)], 3, 4, second);
+        commit0(
+            vec![
+                data(Kind0::Remove, removed, None, "remove"),
+                expand(first, &expanded, "expand"),
+                select(second, &reselected, "select again"),
+            ],
+            true,
+        );
+        let readded: &[u8] = &[2];
+        let reexpanded = draw(&[(6, true, readded)], 5, 5, second)[4..].to_vec();
+        commit0(
+            vec![
+                data(Kind0::Add, readded, None, "add again"),
+                expand(second, &reexpanded, "expand"),
+            ],
+            true,
+        );
+        post_upgrade();
+        Encode!(&get_blocks(0, length()).blocks).unwrap()
+    }
+
+    // Closed juries keep their panels, as the replay of the chain finds them.
+    #[test]
+    fn closed_panels() {
+        chain();
+        let panel = |index| {
+            get_panel(index)
+                .unwrap()
+                .iter()
+                .map(hex::encode)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(panel(4), ["6a13", "6a0f"]);
+        assert_eq!(panel(5), ["6a0e", "6a00", "6a07"]);
+        assert_eq!(panel(6), ["6300", "6303"]);
+        assert!(matches!(
+            expand(4, 1, vec![]),
+            Err(JuryError::JuryClosed(4))
+        ));
+        assert!(matches!(close(6, vec![]), Err(JuryError::JuryClosed(6))));
+        assert_eq!(open_juries(&None), [3, 14]);
+    }
+
+    // The juries of the first version are closed, without a block recording it.
+    #[test]
+    fn baseline_juries() {
+        baseline_chain();
+        assert_eq!(get_pending(), 0);
+        assert!(matches!(
+            expand(2, 1, vec![]),
+            Err(JuryError::JuryClosed(2))
+        ));
+        assert!(matches!(close(5, vec![]), Err(JuryError::JuryClosed(5))));
+        for (select, expand) in [(2, 4), (5, 7)] {
+            let mut panel = read_data(select).jurors;
+            panel.extend(read_data(expand).jurors);
+            assert_eq!(get_panel(select).unwrap(), panel);
+            assert_eq!(get_panel(expand).unwrap(), panel);
+        }
+        let serving = options(|o| o.exclude_serving = Some(true));
+        let select = run(select(length() - 1, 15, vec![], serving)).unwrap();
+        assert_eq!(read_data(select).serving, Some(vec![]));
+    }
+
+    // A selection excludes the jurors drawn from its pool within a cooldown in
+    // time, found from the selections of that pool only.
+    #[test]
+    fn cooling_index() {
+        canister_init(None, None);
+        let jurors = |prefix: u8| (0..6u8).map(|i| vec![prefix, i]).collect::<Vec<_>>();
+        add(jurors(b'a'), vec![], None, None, None, None).unwrap();
+        add(jurors(b'b'), vec![], Some("b".into()), None, None, None).unwrap();
+        let cooldown = Cooldown::Nanoseconds(10);
+        set_cooldown(Some(cooldown), None).unwrap();
+        let a = run(select(length() - 1, 2, vec![], None)).unwrap();
+        let b_pool = options(|o| o.pool = Some("b".into()));
+        let b = run(select(length() - 1, 3, vec![], b_pool)).unwrap();
+        let e = expand(a, 1, vec![]).unwrap();
+        let mut drawn = read_data(e).jurors;
+        drawn.extend(read_data(a).jurors);
+        let now = api::time();
+        assert_eq!(cooling(&None, Some(cooldown), length(), now), drawn);
+        assert_eq!(cooling(&None, Some(cooldown), e, now), read_data(a).jurors);
+        assert!(cooling(&None, Some(cooldown), length(), now + 10).is_empty());
+        let selections = |pool: &str| {
+            let pool = PoolStorable(pool.to_string());
+            let range = (pool.clone(), 0)..=(pool, u32::MAX);
+            SELECTIONS.with(|s| {
+                s.borrow()
+                    .range(range)
+                    .map(|((_, i), _)| u32::MAX - i)
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(selections(""), [e, a]);
+        assert_eq!(selections("b"), [b]);
+        // Only the three jurors not cooling down remain in the pool.
+        let c = run(select(length() - 1, 3, vec![], None)).unwrap();
+        let mut panel = read_data(c).jurors;
+        panel.extend(drawn);
+        panel.sort();
+        assert_eq!(panel, jurors(b'a'));
+        assert!(matches!(
+            run(select(length() - 1, 1, vec![], None)),
+            Err(JuryError::PoolTooSmall { .. })
+        ));
+    }
+
+    // A canister with a pool of size jurors, returned in order.
+    fn pool_of(size: u8) -> Vec<Blob> {
+        canister_init(None, None);
+        let jurors: Vec<Blob> = (0..size).map(|i| vec![b'j', i]).collect();
+        add(jurors.clone(), vec![], None, None, None, None).unwrap();
+        jurors
+    }
+
+    // Make the following calls from a principal without the Selector role.
+    fn call_as_pool_manager() {
+        let principal = Principal::from_slice(&[2]);
+        authorize(principal, Some(vec![Role::PoolManager])).unwrap();
+        api::set_caller(principal);
+    }
+
+    // The excluded jurors are not drawn, nor counted in the size of the pool.
+    #[test]
+    fn select_errors() {
+        let jurors = pool_of(6);
+        let exclude = options(|o| o.exclude = Some(jurors[..2].to_vec()));
+        assert!(matches!(
+            run(select(length() - 1, 5, vec![], exclude.clone())),
+            Err(JuryError::PoolTooSmall { size: 4, count: 5 })
+        ));
+        assert!(matches!(
+            run(select(length(), 1, vec![], None)),
+            Err(JuryError::IndexOutOfRange {
+                index: 1,
+                length: 1
+            })
+        ));
+        let s = run(select(length() - 1, 4, vec![], exclude)).unwrap();
+        assert!(read_data(s).jurors.iter().all(|j| !jurors[..2].contains(j)));
+        call_as_pool_manager();
+        assert!(matches!(
+            run(select(length() - 1, 1, vec![], None)),
+            Err(JuryError::Unauthorized)
+        ));
+    }
+
+    #[test]
+    fn replace_errors() {
+        let jurors = pool_of(6);
+        let s = run(select(length() - 1, 2, vec![], None)).unwrap();
+        let panel = get_panel(s).unwrap();
+        let outsider = jurors.iter().find(|j| !panel.contains(j)).unwrap();
+        assert!(matches!(
+            replace(s, vec![], vec![]),
+            Err(JuryError::NoJurors)
+        ));
+        assert!(matches!(
+            replace(s, vec![outsider.clone()], vec![]),
+            Err(JuryError::NotOnPanel(j)) if j == *outsider
+        ));
+        assert!(matches!(
+            replace(s, vec![panel[0].clone(), panel[0].clone()], vec![]),
+            Err(JuryError::NotOnPanel(_))
+        ));
+        assert!(matches!(
+            replace(0, panel.clone(), vec![]),
+            Err(JuryError::NotASelection(0))
+        ));
+        assert!(matches!(get_panel(0), Err(JuryError::NotASelection(0))));
+        // Each replacement is a new draw, so the pool runs out after 6 draws.
+        let r = replace(s, panel, vec![]).unwrap();
+        replace(r, get_panel(s).unwrap(), vec![]).unwrap();
+        assert!(matches!(
+            replace(s, get_panel(s).unwrap()[..1].to_vec(), vec![]),
+            Err(JuryError::PoolTooSmall { size: 6, count: 7 })
+        ));
+        close(r, vec![]).unwrap();
+        let panel = get_panel(s).unwrap();
+        assert_eq!(get_panel(r).unwrap(), panel);
+        assert!(matches!(
+            replace(r, panel.clone(), vec![]),
+            Err(JuryError::JuryClosed(i)) if i == r
+        ));
+        call_as_pool_manager();
+        assert!(matches!(
+            replace(s, panel, vec![]),
+            Err(JuryError::Unauthorized)
+        ));
+    }
+
+    // Each party has the strikes of the Select block.
+    #[test]
+    fn strike_errors() {
+        let jurors = pool_of(6);
+        let strikes = options(|o| o.strikes = Some(1));
+        let s = run(select(length() - 1, 3, vec![], strikes)).unwrap();
+        let panel = get_panel(s).unwrap();
+        let outsider = jurors.iter().find(|j| !panel.contains(j)).unwrap();
+        let (party, other) = (Principal::from_slice(&[7]), Principal::from_slice(&[8]));
+        assert!(matches!(
+            strike(s, vec![], party, vec![]),
+            Err(JuryError::NoJurors)
+        ));
+        assert!(matches!(
+            strike(s, vec![outsider.clone()], party, vec![]),
+            Err(JuryError::NotOnPanel(j)) if j == *outsider
+        ));
+        assert!(matches!(
+            strike(s, panel[..2].to_vec(), party, vec![]),
+            Err(JuryError::StrikeLimit { limit: 1, .. })
+        ));
+        strike(s, panel[..1].to_vec(), party, vec![]).unwrap();
+        assert!(matches!(
+            strike(s, panel[1..2].to_vec(), party, vec![]),
+            Err(JuryError::StrikeLimit { party: p, limit: 1 }) if p == party
+        ));
+        // The struck juror has left the panel.
+        assert!(matches!(
+            strike(s, panel[..1].to_vec(), other, vec![]),
+            Err(JuryError::NotOnPanel(_))
+        ));
+        strike(s, panel[1..2].to_vec(), other, vec![]).unwrap();
+        assert_eq!(get_panel(s).unwrap(), panel[2..]);
+        close(s, vec![]).unwrap();
+        let third = Principal::from_slice(&[9]);
+        assert!(matches!(
+            strike(s, panel[2..].to_vec(), third, vec![]),
+            Err(JuryError::JuryClosed(i)) if i == s
+        ));
+        call_as_pool_manager();
+        assert!(matches!(
+            strike(s, panel[2..].to_vec(), third, vec![]),
+            Err(JuryError::Unauthorized)
+        ));
+    }
+
+    // The alternates are drawn after the jurors, count towards the size of the
+    // pool and are seated in order until there are none left.
+    #[test]
+    fn alternate_errors() {
+        let jurors = pool_of(6);
+        let alternates = |count| options(|o| o.alternates = Some(count));
+        assert!(matches!(
+            run(select(length() - 1, 4, vec![], alternates(3))),
+            Err(JuryError::PoolTooSmall { size: 6, count: 7 })
+        ));
+        let quotas = vec![Quota {
+            category: String::new(),
+            count: 1,
+        }];
+        assert!(matches!(
+            run(select_stratified(
+                length() - 1,
+                quotas,
+                vec![],
+                alternates(1)
+            )),
+            Err(JuryError::StratifiedAlternates)
+        ));
+        let s = run(select(length() - 1, 2, vec![], alternates(1))).unwrap();
+        let panel = get_panel(s).unwrap();
+        let outsider = jurors.iter().find(|j| !panel.contains(j)).unwrap();
+        assert!(matches!(
+            expand(s, 4, vec![]),
+            Err(JuryError::PoolTooSmall { count: 7, .. })
+        ));
+        assert!(matches!(
+            expand(s, u32::MAX, vec![]),
+            Err(JuryError::PoolTooSmall {
+                count: u32::MAX,
+                ..
+            })
+        ));
+        assert!(matches!(
+            promote_alternate(s, Some(outsider.clone()), vec![]),
+            Err(JuryError::NotOnPanel(j)) if j == *outsider
+        ));
+        promote_alternate(s, Some(panel[0].clone()), vec![]).unwrap();
+        assert!(matches!(
+            promote_alternate(s, None, vec![]),
+            Err(JuryError::NoAlternates(i)) if i == s
+        ));
+        close(s, vec![]).unwrap();
+        for result in [promote_alternate(s, None, vec![]), expand(s, 1, vec![])] {
+            assert!(matches!(result, Err(JuryError::JuryClosed(i)) if i == s));
+        }
+        call_as_pool_manager();
+        assert!(matches!(
+            promote_alternate(s, None, vec![]),
+            Err(JuryError::Unauthorized)
+        ));
+        assert!(matches!(expand(s, 1, vec![]), Err(JuryError::Unauthorized)));
+    }
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "verifier", "fixtures", name]
+            .iter()
+            .collect()
+    }
+
+    // The verifier tests check the chains recorded from this version of the
+    // canister.  Rerun record_fixtures if it records different blocks.
+    #[test]
+    fn fixtures() {
+        assert!(chain() == std::fs::read(fixture("chain.bin")).unwrap());
+        let baseline = std::thread::spawn(baseline_chain).join().unwrap();
+        assert!(baseline == std::fs::read(fixture("baseline_chain.bin")).unwrap());
+    }
+
+    #[test]
+    #[ignore]
+    fn record_fixtures() {
+        std::fs::write(fixture("chain.bin"), chain()).unwrap();
+        let baseline = std::thread::spawn(baseline_chain).join().unwrap();
+        std::fs::write(fixture("baseline_chain.bin"), baseline).unwrap();
+    }
+}
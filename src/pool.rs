@@ -0,0 +1,420 @@
+// Versioned juror pool: a persistent order statistic treap keyed by juror.
+//
+// Each Add/Remove block produces a new version which shares structure with
+// the previous ones, so the size, rank and nth juror of the pool at any log
+// index are O(log n).  Each node also has the selection weight of its juror and
+// the total weight of its subtree, so a weighted draw is O(log n) too.  Nodes
+// created since the last version are not yet shared and are updated in place,
+// so a large Add block does not copy paths.
+
+use sha2::Digest;
+
+type Blob = Vec<u8>;
+
+const NIL: u32 = 0;
+
+#[derive(Clone, Copy, Default)]
+struct Node {
+    key: u32,
+    priority: u32,
+    left: u32,
+    right: u32,
+    size: u32,
+    weight: u32,
+    // The total weight of the subtree.
+    total: u64,
+}
+
+pub struct Pool {
+    keys: Vec<Blob>,
+    nodes: Vec<Node>,
+    // (log index, root) in increasing log index order.
+    versions: Vec<(u32, u32)>,
+    // Nodes below this are shared by a version and must be copied on write.
+    frozen: u32,
+    root: u32,
+}
+
+impl Default for Pool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pool {
+    pub fn new() -> Self {
+        Pool {
+            keys: Vec::new(),
+            nodes: vec![Node::default()],
+            versions: Vec::new(),
+            frozen: 1,
+            root: NIL,
+        }
+    }
+
+    // Add a juror with a selection weight to the pool as of log index.  The
+    // juror must not be present.
+    pub fn insert(&mut self, index: u32, juror: &[u8], weight: u32) {
+        self.begin(index);
+        let (l, r) = self.split(self.root, juror);
+        self.keys.push(juror.to_vec());
+        let priority: [u8; 32] = sha2::Sha256::digest(juror).into();
+        let node = self.alloc(Node {
+            key: (self.keys.len() - 1) as u32,
+            priority: u32::from_le_bytes(priority[..4].try_into().unwrap()),
+            left: NIL,
+            right: NIL,
+            size: 1,
+            weight,
+            total: weight as u64,
+        });
+        let l = self.merge(l, node);
+        self.root = self.merge(l, r);
+        self.end(index);
+    }
+
+    // Remove a juror from the pool as of log index.  The juror must be present.
+    pub fn remove(&mut self, index: u32, juror: &[u8]) {
+        self.begin(index);
+        let (l, r) = self.split(self.root, juror);
+        let r = self.remove_first(r);
+        self.root = self.merge(l, r);
+        self.end(index);
+    }
+
+    // Number of jurors in the pool at log index.
+    pub fn size(&self, index: u32) -> u32 {
+        self.nodes[self.root_at(index) as usize].size
+    }
+
+    // The total weight of the jurors in the pool at log index.
+    pub fn weight(&self, index: u32) -> u64 {
+        self.nodes[self.root_at(index) as usize].total
+    }
+
+    // The weight of the juror at position n of the pool at log index.
+    pub fn nth_weight(&self, index: u32, n: u32) -> Option<u32> {
+        self.find_nth(index, n)
+            .map(|t| self.nodes[t as usize].weight)
+    }
+
+    // The first position of the pool at log index at which the cumulative
+    // weight of the jurors, less removed(position), exceeds target.  removed()
+    // must be non-decreasing and at most the cumulative weight at each position,
+    // e.g. the weight of jurors up to the position which are not to be drawn.
+    pub fn find_weight(
+        &self,
+        index: u32,
+        target: u64,
+        removed: impl Fn(u32) -> u64,
+    ) -> Option<u32> {
+        let mut t = self.root_at(index);
+        let (mut rank, mut cumulative) = (0, 0);
+        let mut found = None;
+        while t != NIL {
+            let node = &self.nodes[t as usize];
+            let left = &self.nodes[node.left as usize];
+            let position = rank + left.size;
+            let weight = cumulative + left.total + node.weight as u64;
+            if weight - removed(position) > target {
+                found = Some(position);
+                t = node.left;
+            } else {
+                rank = position + 1;
+                cumulative = weight;
+                t = node.right;
+            }
+        }
+        found
+    }
+
+    // The juror at position n of the pool at log index.
+    pub fn nth(&self, index: u32, n: u32) -> Option<&Blob> {
+        self.find_nth(index, n)
+            .map(|t| &self.keys[self.nodes[t as usize].key as usize])
+    }
+
+    fn find_nth(&self, index: u32, mut n: u32) -> Option<u32> {
+        let mut t = self.root_at(index);
+        while t != NIL {
+            let node = &self.nodes[t as usize];
+            let left_size = self.nodes[node.left as usize].size;
+            if n < left_size {
+                t = node.left;
+            } else if n == left_size {
+                return Some(t);
+            } else {
+                n -= left_size + 1;
+                t = node.right;
+            }
+        }
+        None
+    }
+
+    // The position of juror in the pool at log index.
+    pub fn rank(&self, index: u32, juror: &[u8]) -> Option<u32> {
+        let mut t = self.root_at(index);
+        let mut rank = 0;
+        while t != NIL {
+            let node = &self.nodes[t as usize];
+            let left_size = self.nodes[node.left as usize].size;
+            match juror.cmp(&self.keys[node.key as usize]) {
+                std::cmp::Ordering::Less => t = node.left,
+                std::cmp::Ordering::Equal => return Some(rank + left_size),
+                std::cmp::Ordering::Greater => {
+                    rank += left_size + 1;
+                    t = node.right;
+                }
+            }
+        }
+        None
+    }
+
+    // Up to length jurors from position start of the pool at log index.
+    pub fn range(&self, index: u32, start: u32, length: u32) -> Vec<Blob> {
+        let mut result = Vec::new();
+        self.collect(self.root_at(index), start, length, &mut result);
+        result
+    }
+
+    fn collect(&self, t: u32, start: u32, length: u32, result: &mut Vec<Blob>) {
+        if t == NIL || result.len() as u32 >= length {
+            return;
+        }
+        let node = &self.nodes[t as usize];
+        let left_size = self.nodes[node.left as usize].size;
+        if start < left_size {
+            self.collect(node.left, start, length, result);
+        }
+        if start <= left_size && (result.len() as u32) < length {
+            result.push(self.keys[node.key as usize].clone());
+        }
+        self.collect(
+            node.right,
+            start.saturating_sub(left_size + 1),
+            length,
+            result,
+        );
+    }
+
+    fn root_at(&self, index: u32) -> u32 {
+        match self.versions.partition_point(|v| v.0 <= index) {
+            0 => NIL,
+            i => self.versions[i - 1].1,
+        }
+    }
+
+    fn begin(&mut self, index: u32) {
+        if let Some(last) = self.versions.last() {
+            assert!(last.0 <= index, "pool changes must be in log order");
+            if last.0 < index {
+                // Seal the previous version.
+                self.frozen = self.nodes.len() as u32;
+            }
+        }
+    }
+
+    fn end(&mut self, index: u32) {
+        match self.versions.last_mut() {
+            Some(last) if last.0 == index => last.1 = self.root,
+            _ => self.versions.push((index, self.root)),
+        }
+    }
+
+    fn alloc(&mut self, node: Node) -> u32 {
+        self.nodes.push(node);
+        (self.nodes.len() - 1) as u32
+    }
+
+    // Return a node which may be updated in place, copying it if it is shared.
+    fn own(&mut self, t: u32) -> u32 {
+        if t >= self.frozen {
+            t
+        } else {
+            let node = self.nodes[t as usize];
+            self.alloc(node)
+        }
+    }
+
+    fn update(&mut self, t: u32) {
+        let node = self.nodes[t as usize];
+        let (left, right) = (
+            self.nodes[node.left as usize],
+            self.nodes[node.right as usize],
+        );
+        self.nodes[t as usize].size = left.size + right.size + 1;
+        self.nodes[t as usize].total = left.total + right.total + node.weight as u64;
+    }
+
+    // Split into jurors less than juror and the rest.
+    fn split(&mut self, t: u32, juror: &[u8]) -> (u32, u32) {
+        if t == NIL {
+            return (NIL, NIL);
+        }
+        let t = self.own(t);
+        let node = self.nodes[t as usize];
+        if self.keys[node.key as usize].as_slice() < juror {
+            let (l, r) = self.split(node.right, juror);
+            self.nodes[t as usize].right = l;
+            self.update(t);
+            (t, r)
+        } else {
+            let (l, r) = self.split(node.left, juror);
+            self.nodes[t as usize].left = r;
+            self.update(t);
+            (l, t)
+        }
+    }
+
+    fn merge(&mut self, l: u32, r: u32) -> u32 {
+        if l == NIL {
+            return r;
+        }
+        if r == NIL {
+            return l;
+        }
+        if self.nodes[l as usize].priority > self.nodes[r as usize].priority {
+            let l = self.own(l);
+            let right = self.merge(self.nodes[l as usize].right, r);
+            self.nodes[l as usize].right = right;
+            self.update(l);
+            l
+        } else {
+            let r = self.own(r);
+            let left = self.merge(l, self.nodes[r as usize].left);
+            self.nodes[r as usize].left = left;
+            self.update(r);
+            r
+        }
+    }
+
+    fn remove_first(&mut self, t: u32) -> u32 {
+        assert!(t != NIL, "juror not in pool");
+        let node = self.nodes[t as usize];
+        if node.left == NIL {
+            return node.right;
+        }
+        let t = self.own(t);
+        let left = self.remove_first(node.left);
+        self.nodes[t as usize].left = left;
+        self.update(t);
+        t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use rand_core::SeedableRng;
+    use std::collections::BTreeMap;
+
+    // The jurors of the pool at index and their weights, in order.
+    fn jurors(pool: &Pool, index: u32) -> Vec<(Blob, u32)> {
+        (0..pool.size(index))
+            .map(|n| {
+                (
+                    pool.nth(index, n).unwrap().clone(),
+                    pool.nth_weight(index, n).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn versions() {
+        let mut rng = rand_chacha::ChaCha20Rng::from_seed([1; 32]);
+        let mut pool = Pool::new();
+        let mut model: BTreeMap<Blob, u32> = BTreeMap::new();
+        let mut snapshots = Vec::new();
+        for index in (0..200).step_by(2) {
+            // Several changes at the same log index form one version.
+            for _ in 0..rng.gen_range(1..20) {
+                let juror = vec![rng.gen_range(0..64u8)];
+                if model.remove(&juror).is_some() {
+                    pool.remove(index, &juror);
+                } else {
+                    let weight = rng.gen_range(0..5);
+                    pool.insert(index, &juror, weight);
+                    model.insert(juror, weight);
+                }
+            }
+            snapshots.push((index, model.clone()));
+        }
+        assert_eq!(pool.size(0), snapshots[0].1.len() as u32);
+        for (index, model) in snapshots {
+            let expected: Vec<(Blob, u32)> = model.into_iter().collect();
+            // Indexes between versions see the earlier version.
+            for index in [index, index + 1] {
+                assert_eq!(jurors(&pool, index), expected);
+                let weight: u64 = expected.iter().map(|(_, w)| *w as u64).sum();
+                assert_eq!(pool.weight(index), weight);
+                for (n, (juror, _)) in expected.iter().enumerate() {
+                    assert_eq!(pool.rank(index, juror), Some(n as u32));
+                }
+                assert_eq!(pool.rank(index, &[64]), None);
+                let all: Vec<Blob> = expected.iter().map(|(j, _)| j.clone()).collect();
+                assert_eq!(pool.range(index, 0, u32::MAX), all);
+                assert_eq!(
+                    pool.range(index, 3, 5),
+                    all.iter().skip(3).take(5).cloned().collect::<Vec<_>>()
+                );
+                assert_eq!(pool.nth(index, all.len() as u32), None);
+            }
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let mut pool = Pool::new();
+        pool.insert(5, &[1], 1);
+        assert_eq!(pool.size(4), 0);
+        assert_eq!(pool.weight(4), 0);
+        assert_eq!(pool.nth(4, 0), None);
+        assert_eq!(pool.size(5), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "pool changes must be in log order")]
+    fn out_of_order() {
+        let mut pool = Pool::new();
+        pool.insert(5, &[1], 1);
+        pool.insert(4, &[2], 1);
+    }
+
+    #[test]
+    fn find_weight() {
+        let mut rng = rand_chacha::ChaCha20Rng::from_seed([2; 32]);
+        let mut pool = Pool::new();
+        for juror in 0..100u8 {
+            pool.insert(0, &[juror], rng.gen_range(0..4));
+        }
+        let weights: Vec<u64> = jurors(&pool, 0).iter().map(|(_, w)| *w as u64).collect();
+        for _ in 0..50 {
+            let removed: Vec<bool> = (0..100).map(|_| rng.gen_bool(0.3)).collect();
+            // The weight of the removed jurors at or before each position.
+            let mut removed_to = Vec::new();
+            let mut sum = 0;
+            for (weight, removed) in weights.iter().zip(&removed) {
+                sum += if *removed { *weight } else { 0 };
+                removed_to.push(sum);
+            }
+            let remaining: u64 = weights.iter().sum::<u64>() - sum;
+            for target in 0..=remaining {
+                // The first remaining position whose cumulative weight exceeds target.
+                let mut cumulative = 0;
+                let expected = (0..100).find(|p| {
+                    if !removed[*p] {
+                        cumulative += weights[*p];
+                    }
+                    cumulative > target
+                });
+                let found = pool.find_weight(0, target, |p| removed_to[p as usize]);
+                assert_eq!(found, expected.map(|p| p as u32));
+                if let Some(p) = found {
+                    assert!(!removed[p as usize] && weights[p as usize] > 0);
+                }
+            }
+        }
+    }
+}
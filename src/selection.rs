@@ -0,0 +1,579 @@
+// Jury selection shared by the canister and the offline replay.
+
+use crate::block::{Cooldown, Data, Kind, Quota};
+use crate::pool::Pool;
+use rand::distributions::Distribution;
+use rand::distributions::Uniform;
+use rand::Rng;
+use rand_core::SeedableRng;
+use sha2::Digest;
+use std::collections::{HashMap, HashSet};
+
+type Blob = Vec<u8>;
+type Hash = [u8; 32];
+
+// Algorithm of Select blocks drawing in proportion to the juror weights.
+pub const WEIGHTED_ALGORITHM: u32 = 3;
+// Algorithm of Select blocks drawing quotas of jurors by category.
+pub const STRATIFIED_ALGORITHM: u32 = 4;
+
+// Draw count jurors from the pool at index less the excluded jurors.
+// Returns None if the algorithm is unknown or the pool is too small.
+pub fn make_jury(
+    pool: &Pool,
+    index: u32,
+    count: u32,
+    seed: Hash,
+    algorithm: Option<u32>,
+    exclude: &[Blob],
+) -> Option<Vec<Blob>> {
+    let mut rng = make_rng(seed);
+    let excluded = excluded_ranks(pool, index, exclude);
+    if algorithm == Some(WEIGHTED_ALGORITHM) {
+        let sample = sample_weighted(&mut rng, pool, index, count as usize, &excluded)?;
+        return Some(
+            sample
+                .into_iter()
+                .map(|r| pool.nth(index, r).unwrap().clone())
+                .collect(),
+        );
+    }
+    let sample = sample(
+        &mut rng,
+        algorithm,
+        count as usize,
+        pool.size(index) as usize - excluded.len(),
+    )?;
+    Some(
+        sample
+            .into_iter()
+            .map(|s| pool.nth(index, skip(s as u32, &excluded)).unwrap().clone())
+            .collect(),
+    )
+}
+
+// Seat the jurors drawn by a Select, Expand or Replace block on the panel of
+// its jury: replacements take the seats of the jurors they replace.
+fn seat(panel: &mut Vec<Blob>, jurors: &[Blob], jurors_out: Option<&[Blob]>) {
+    let Some(jurors_out) = jurors_out else {
+        for juror in jurors {
+            if !panel.contains(juror) {
+                panel.push(juror.clone());
+            }
+        }
+        return;
+    };
+    for (out, juror) in jurors_out.iter().zip(jurors) {
+        if let Some(seat) = panel.iter().position(|j| j == out) {
+            panel[seat] = juror.clone();
+        }
+    }
+}
+
+// The panel of a jury from its blocks in log order and the number of jurors
+// drawn for it, including those replaced.  Struck jurors leave the panel.
+pub fn panel(blocks: &[Data]) -> (Vec<Blob>, u32) {
+    let mut panel = Vec::new();
+    let mut drawn: Vec<&Blob> = Vec::new();
+    for data in blocks {
+        if let Kind::Strike = data.kind {
+            panel.retain(|juror| !data.jurors.contains(juror));
+            continue;
+        }
+        seat(&mut panel, &data.jurors, data.jurors_out.as_deref());
+        for juror in data.jurors.iter().chain(data.alternates.iter().flatten()) {
+            if !drawn.contains(&juror) {
+                drawn.push(juror);
+            }
+        }
+    }
+    (panel, drawn.len() as u32)
+}
+
+// The alternates of a jury from its blocks in log order not yet seated, in the
+// order they are seated.
+pub fn alternates(blocks: &[Data]) -> Vec<Blob> {
+    let mut alternates = blocks[0].alternates.clone().unwrap_or_default();
+    for data in blocks.iter().filter(|d| matches!(d.kind, Kind::Promote)) {
+        alternates.retain(|juror| !data.jurors.contains(juror));
+    }
+    alternates
+}
+
+// The jurors on the panels of the open juries of a pool, given by their blocks
+// in order of their Select blocks, and their alternates not yet seated, in the
+// order they were drawn, each once.
+pub fn serving<J: AsRef<[Data]>>(juries: impl IntoIterator<Item = J>) -> Vec<Blob> {
+    let mut jurors = Vec::new();
+    for jury in juries {
+        let jury = jury.as_ref();
+        for juror in panel(jury).0.into_iter().chain(alternates(jury)) {
+            if !jurors.contains(&juror) {
+                jurors.push(juror);
+            }
+        }
+    }
+    jurors
+}
+
+// The jurors excluded from the pool by a Select block and the blocks extending
+// its jury: those listed, those cooling down and those serving.
+pub fn excluded(data: &Data, cooling: Vec<Blob>) -> Vec<Blob> {
+    let mut excluded = data.exclude.clone().unwrap_or_default();
+    excluded.extend(cooling);
+    excluded.extend(data.serving.clone().unwrap_or_default());
+    excluded
+}
+
+// Whether the jurors drawn by the selection at log index at, staged at time
+// (None for blocks before staging times were recorded), are cooling down for
+// a selection at index staged at now.  Scanning back from index this is false
+// for every selection after the first for which it is false.
+pub fn cooling_down(cooldown: &Cooldown, index: u32, now: u64, at: u32, time: Option<u64>) -> bool {
+    match *cooldown {
+        Cooldown::Blocks(blocks) => index - at <= blocks,
+        Cooldown::Nanoseconds(nanoseconds) => {
+            time.is_some_and(|t| now.saturating_sub(t) < nanoseconds)
+        }
+    }
+}
+
+// The ranks in the pool at index of the excluded jurors in it, ascending.
+pub fn excluded_ranks(pool: &Pool, index: u32, exclude: &[Blob]) -> Vec<u32> {
+    let mut ranks: Vec<u32> = exclude.iter().filter_map(|j| pool.rank(index, j)).collect();
+    ranks.sort_unstable();
+    ranks.dedup();
+    ranks
+}
+
+// The rank in the pool of the nth juror which is not excluded.
+fn skip(n: u32, excluded: &[u32]) -> u32 {
+    let mut rank = n;
+    for r in excluded {
+        if *r > rank {
+            break;
+        }
+        rank += 1;
+    }
+    rank
+}
+
+// The per-jury token of a juror added with secret on a jury drawn with rand:
+// sha256(secret || rand).  Only the juror and the canister know the secret,
+// and revealing the token shows nothing of it or of the juror's other juries.
+pub fn token(secret: &[u8], rand: &[u8]) -> Blob {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(secret);
+    hasher.update(rand);
+    hasher.finalize().to_vec()
+}
+
+// The per-jury blinded id of the juror with token: sha256(token).
+pub fn blinded_id(token: &[u8]) -> Blob {
+    sha2::Sha256::digest(token).to_vec()
+}
+
+// The blinded ids of the jurors drawn with rand who were added with a secret,
+// sorted so that they are not aligned with the jurors.
+pub fn blind(rand: &[u8], secrets: &[Option<Blob>]) -> Vec<Blob> {
+    let mut ids: Vec<Blob> = secrets
+        .iter()
+        .flatten()
+        .map(|s| blinded_id(&token(s, rand)))
+        .collect();
+    ids.sort();
+    ids
+}
+
+// The commitment to a juror secret recorded in Add blocks.
+pub fn commitment(secret: &[u8]) -> Blob {
+    sha2::Sha256::digest(secret).to_vec()
+}
+
+// Whether the juror with token is among the blinded ids of a jury.
+pub fn find_token(blinded: &[Blob], token: &[u8]) -> bool {
+    blinded.contains(&blinded_id(token))
+}
+
+// Draw the jurors of each quota in turn from its stratum, the pool of the
+// jurors of its category (ordered by juror bytes) at index less the excluded
+// jurors, each stratum sampled as algorithm 2 continuing the one stream.
+// strata are the pools of the categories of the quotas, in the same order.
+// Returns None if a stratum is too small.
+pub fn make_stratified_jury(
+    strata: &[&Pool],
+    index: u32,
+    seed: Hash,
+    quotas: &[Quota],
+    exclude: &[Blob],
+) -> Option<Vec<Blob>> {
+    let mut rng = make_rng(seed);
+    let mut result = Vec::new();
+    for (stratum, quota) in strata.iter().zip(quotas) {
+        let excluded = excluded_ranks(stratum, index, exclude);
+        let length = (stratum.size(index) as usize) - excluded.len();
+        let sample = sample(&mut rng, Some(2), quota.count as usize, length)?;
+        result.extend(sample.into_iter().map(|s| {
+            stratum
+                .nth(index, skip(s as u32, &excluded))
+                .unwrap()
+                .clone()
+        }));
+    }
+    Some(result)
+}
+
+pub fn make_rng(seed: Hash) -> rand_chacha::ChaCha20Rng {
+    rand_chacha::ChaCha20Rng::from_seed(seed)
+}
+
+// Sample amount distinct indexes from 0..length of the pool (ordered by juror
+// bytes) using the ChaCha20 stream from make_rng().  Every algorithm is kept so
+// that old blocks can be recomputed bit-for-bit, and each is prefix stable so
+// Expand can extend a selection.
+//   0 (or None): sampling with rejection, slow when amount is near length.
+//      It uses rand's Uniform over u32 (usize on wasm32) and so depends on the
+//      pinned rand version.
+//   2: partial Fisher-Yates shuffle, O(amount) regardless of length, using
+//      uniform() which depends only on the ChaCha20 stream.
+// Algorithm 3 (WEIGHTED_ALGORITHM) is sample_weighted().
+// Returns None if the algorithm is unknown or amount is more than length.
+pub fn sample<R>(
+    rng: &mut R,
+    algorithm: Option<u32>,
+    amount: usize,
+    length: usize,
+) -> Option<Vec<usize>>
+where
+    R: Rng + ?Sized,
+{
+    if amount > length {
+        return None;
+    }
+    match algorithm.unwrap_or(0) {
+        0 => Some(sample_rejection(rng, amount, length)),
+        2 => Some(sample_fisher_yates(rng, amount, length)),
+        _ => None,
+    }
+}
+
+// Uniform in low..high: draw 64 bit words, rejecting those in the final partial
+// multiple of the range, and return low plus the remainder.
+fn uniform<R>(rng: &mut R, low: usize, high: usize) -> usize
+where
+    R: Rng + ?Sized,
+{
+    low + uniform_u64(rng, (high - low) as u64) as usize
+}
+
+// Uniform in 0..range, see uniform().
+fn uniform_u64<R>(rng: &mut R, range: u64) -> u64
+where
+    R: Rng + ?Sized,
+{
+    let reject = (u64::MAX % range + 1) % range;
+    loop {
+        let v = rng.next_u64();
+        if v <= u64::MAX - reject {
+            return v % range;
+        }
+    }
+}
+
+// Sample amount distinct positions of the pool at index less the excluded
+// positions (ascending) without replacement, each draw choosing among the
+// remaining jurors in proportion to their weight: take r from uniform_u64()
+// over the total remaining weight and choose the first remaining juror whose
+// cumulative weight exceeds r.  Each draw continues the stream so the sample
+// is prefix stable.  Jurors with weight 0 are never chosen.  Returns None if
+// fewer than amount remaining jurors have a positive weight.
+pub fn sample_weighted<R>(
+    rng: &mut R,
+    pool: &Pool,
+    index: u32,
+    amount: usize,
+    excluded: &[u32],
+) -> Option<Vec<u32>>
+where
+    R: Rng + ?Sized,
+{
+    let mut removed = Removed::default();
+    let mut total = pool.weight(index);
+    for position in excluded {
+        total -= removed.insert(pool, index, *position);
+    }
+    let mut positions = Vec::with_capacity(amount);
+    for _ in 0..amount {
+        if total == 0 {
+            return None;
+        }
+        let r = uniform_u64(rng, total);
+        let position = pool.find_weight(index, r, |p| removed.weight_to(p))?;
+        total -= removed.insert(pool, index, position);
+        positions.push(position);
+    }
+    Some(positions)
+}
+
+// The positions of a pool not to be drawn by sample_weighted(), ascending, with
+// the cumulative weight of the jurors at them.
+#[derive(Default)]
+struct Removed {
+    positions: Vec<u32>,
+    cumulative: Vec<u64>,
+}
+
+impl Removed {
+    // Remove the juror at position of the pool at index and return its weight.
+    fn insert(&mut self, pool: &Pool, index: u32, position: u32) -> u64 {
+        let weight = pool.nth_weight(index, position).unwrap() as u64;
+        let before = self.weight_to(position);
+        let i = self.positions.partition_point(|p| *p < position);
+        self.positions.insert(i, position);
+        self.cumulative.insert(i, before);
+        for c in &mut self.cumulative[i..] {
+            *c += weight;
+        }
+        weight
+    }
+
+    // The weight of the removed jurors at or before position.
+    fn weight_to(&self, position: u32) -> u64 {
+        match self.positions.partition_point(|p| *p <= position) {
+            0 => 0,
+            i => self.cumulative[i - 1],
+        }
+    }
+}
+
+fn sample_fisher_yates<R>(rng: &mut R, amount: usize, length: usize) -> Vec<usize>
+where
+    R: Rng + ?Sized,
+{
+    assert!(amount <= length);
+    // Sparse representation of the swapped positions of 0..length.
+    let mut swapped: HashMap<usize, usize> = HashMap::with_capacity(amount);
+    let mut indices = Vec::with_capacity(amount);
+    for i in 0..amount {
+        let j = uniform(rng, i, length);
+        let pos = *swapped.get(&j).unwrap_or(&j);
+        swapped.insert(j, *swapped.get(&i).unwrap_or(&i));
+        indices.push(pos);
+    }
+    indices
+}
+
+fn sample_rejection<R, IndexVec>(rng: &mut R, amount: usize, length: usize) -> IndexVec
+where
+    R: Rng + ?Sized,
+    IndexVec: From<Vec<usize>>,
+{
+    assert!(amount <= length);
+    let mut cache = HashSet::with_capacity(amount);
+    let distr = Uniform::new(0, length as u32);
+    let mut indices = Vec::with_capacity(amount);
+    for _ in 0..amount {
+        let mut pos = distr.sample(rng) as usize;
+        while !cache.insert(pos) {
+            pos = distr.sample(rng) as usize;
+        }
+        indices.push(pos);
+    }
+    IndexVec::from(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A pool at index 0 of jurors [0]..[size] with weight juror % 4.
+    fn pool(size: u8) -> Pool {
+        let mut pool = Pool::new();
+        for juror in 0..size {
+            pool.insert(0, &[juror], (juror % 4) as u32);
+        }
+        pool
+    }
+
+    // Recorded blocks are recomputed bit-for-bit, so the samples must not change.
+    #[test]
+    fn pinned() {
+        let expected: [(Option<u32>, [usize; 6]); 2] = [
+            (None, [33, 3, 43, 24, 29, 0]),
+            (Some(2), [16, 39, 38, 33, 36, 14]),
+        ];
+        for (algorithm, expected) in expected {
+            let mut rng = make_rng([3; 32]);
+            assert_eq!(sample(&mut rng, algorithm, 6, 50).unwrap(), expected);
+        }
+        let mut rng = make_rng([3; 32]);
+        let sample = sample_weighted(&mut rng, &pool(20), 0, 6, &[]).unwrap();
+        assert_eq!(sample, [18, 17, 9, 14, 11, 3]);
+    }
+
+    #[test]
+    fn prefix_stable() {
+        let pool = pool(60);
+        let exclude = vec![vec![1], vec![10], vec![30], vec![100]];
+        for algorithm in [None, Some(2), Some(WEIGHTED_ALGORITHM)] {
+            for seed in 0..10 {
+                let seed = [seed; 32];
+                let all = make_jury(&pool, 0, 30, seed, algorithm, &exclude).unwrap();
+                for count in 0..30 {
+                    let jury = make_jury(&pool, 0, count, seed, algorithm, &exclude).unwrap();
+                    assert_eq!(jury, all[..count as usize]);
+                }
+                let mut distinct = all.clone();
+                distinct.sort();
+                distinct.dedup();
+                assert_eq!(distinct.len(), all.len());
+                assert!(all.iter().all(|j| !exclude.contains(j)));
+            }
+        }
+    }
+
+    #[test]
+    fn too_small() {
+        let pool = pool(10);
+        let exclude = vec![vec![0], vec![1]];
+        assert_eq!(
+            make_jury(&pool, 0, 8, [0; 32], Some(2), &exclude)
+                .unwrap()
+                .len(),
+            8
+        );
+        assert_eq!(make_jury(&pool, 0, 9, [0; 32], Some(2), &exclude), None);
+        assert_eq!(make_jury(&pool, 0, 1, [0; 32], Some(1), &[]), None);
+        assert_eq!(make_jury(&pool, 0, 1, [0; 32], Some(5), &[]), None);
+        let mut rng = make_rng([0; 32]);
+        assert_eq!(sample(&mut rng, Some(2), 11, 10), None);
+    }
+
+    #[test]
+    fn weighted() {
+        // Jurors [0], [4] and [8] have weight 0.
+        let pool = pool(10);
+        for seed in 0..50 {
+            let jury = make_jury(&pool, 0, 7, [seed; 32], Some(WEIGHTED_ALGORITHM), &[]).unwrap();
+            assert!(jury.iter().all(|j| j[0] % 4 != 0));
+        }
+        assert_eq!(
+            make_jury(&pool, 0, 8, [0; 32], Some(WEIGHTED_ALGORITHM), &[]),
+            None
+        );
+        let exclude = vec![vec![1]];
+        assert_eq!(
+            make_jury(&pool, 0, 7, [0; 32], Some(WEIGHTED_ALGORITHM), &exclude),
+            None
+        );
+        // The draws follow the weights: [3] and [7] (weight 3) before [1] and [5] (weight 1).
+        let mut counts = [0; 10];
+        for seed in 0..200 {
+            let jury = make_jury(&pool, 0, 1, [seed; 32], Some(WEIGHTED_ALGORITHM), &[]).unwrap();
+            counts[jury[0][0] as usize] += 1;
+        }
+        assert!(counts[3] + counts[7] > 2 * (counts[1] + counts[5]));
+    }
+
+    #[test]
+    fn stratified() {
+        let mut north = Pool::new();
+        let mut south = Pool::new();
+        for juror in 0..20u8 {
+            match juror % 2 {
+                0 => north.insert(0, &[juror], 1),
+                _ => south.insert(0, &[juror], 1),
+            }
+        }
+        let quotas = [
+            Quota {
+                category: "north".into(),
+                count: 3,
+            },
+            Quota {
+                category: "south".into(),
+                count: 2,
+            },
+        ];
+        let exclude = vec![vec![0], vec![1]];
+        let jury = make_stratified_jury(&[&north, &south], 0, [0; 32], &quotas, &exclude).unwrap();
+        assert_eq!(jury.len(), 5);
+        assert!(jury[..3].iter().all(|j| j[0] % 2 == 0));
+        assert!(jury[3..].iter().all(|j| j[0] % 2 == 1));
+        assert!(jury.iter().all(|j| !exclude.contains(j)));
+        // The first stratum is drawn as algorithm 2 from the one stream.
+        let first = make_jury(&north, 0, 3, [0; 32], Some(2), &exclude).unwrap();
+        assert_eq!(jury[..3], first);
+        let quotas = [Quota {
+            category: "south".into(),
+            count: 10,
+        }];
+        assert_eq!(
+            make_stratified_jury(&[&south], 0, [0; 32], &quotas, &exclude),
+            None
+        );
+    }
+
+    #[test]
+    fn tokens() {
+        let rand = [5; 32];
+        let secrets = [Some(vec![1; 32]), None, Some(vec![2; 32])];
+        let blinded = blind(&rand, &secrets);
+        assert_eq!(blinded.len(), 2);
+        assert!(blinded[0] < blinded[1]);
+        assert!(find_token(&blinded, &token(&[1; 32], &rand)));
+        assert!(find_token(&blinded, &token(&[2; 32], &rand)));
+        assert!(!find_token(&blinded, &token(&[1; 32], &[6; 32])));
+        assert!(!find_token(&blinded, &token(&[3; 32], &rand)));
+        assert_ne!(commitment(&[1; 32]), blinded_id(&token(&[1; 32], &rand)));
+    }
+
+    #[test]
+    fn seats() {
+        let mut panel = vec![vec![1], vec![2], vec![3]];
+        seat(&mut panel, &[vec![4], vec![2]], None);
+        assert_eq!(panel, [vec![1], vec![2], vec![3], vec![4]]);
+        seat(&mut panel, &[vec![5]], Some(&[vec![2]]));
+        assert_eq!(panel, [vec![1], vec![5], vec![3], vec![4]]);
+    }
+
+    // A jury drawing [1], [2] and [3] with alternates [4] and [5], replacing
+    // [2] with [6], striking [1] and seating [4] in the seat of [3].
+    #[test]
+    fn jury() {
+        let block = |kind, jurors: &[u8], jurors_out: Option<&[u8]>| Data {
+            kind,
+            jurors: jurors.iter().map(|j| vec![*j]).collect(),
+            jurors_out: jurors_out.map(|out| out.iter().map(|j| vec![*j]).collect()),
+            ..Default::default()
+        };
+        let mut blocks = vec![Data {
+            alternates: Some(vec![vec![4], vec![5]]),
+            serving: Some(vec![vec![7]]),
+            ..block(Kind::Select, &[1, 2, 3], None)
+        }];
+        blocks.push(block(Kind::Replace, &[6], Some(&[2])));
+        assert_eq!(panel(&blocks), (vec![vec![1], vec![6], vec![3]], 6));
+        blocks.push(block(Kind::Strike, &[1], None));
+        blocks.push(block(Kind::Promote, &[4], Some(&[3])));
+        assert_eq!(panel(&blocks), (vec![vec![6], vec![4]], 6));
+        assert_eq!(alternates(&blocks), [vec![5]]);
+        let other = vec![block(Kind::Select, &[5, 8], None)];
+        assert_eq!(
+            serving([&blocks, &other]),
+            [vec![6], vec![4], vec![5], vec![8]]
+        );
+        assert_eq!(excluded(&blocks[0], vec![vec![9]]), [vec![9], vec![7]]);
+    }
+
+    #[test]
+    fn cooldown() {
+        let blocks = Cooldown::Blocks(3);
+        assert!(cooling_down(&blocks, 10, 0, 7, None));
+        assert!(!cooling_down(&blocks, 10, 0, 6, None));
+        let nanoseconds = Cooldown::Nanoseconds(100);
+        assert!(cooling_down(&nanoseconds, 10, 150, 9, Some(51)));
+        assert!(!cooling_down(&nanoseconds, 10, 150, 9, Some(50)));
+        assert!(!cooling_down(&nanoseconds, 10, 150, 9, None));
+    }
+}
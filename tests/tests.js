@@ -65,6 +65,17 @@ function blockToHex(block) {
   };
 }
 
+// The opt SelectOptions of the select methods with the given fields set.
+function selectOptions(options) {
+  return [{ pool: [], exclude: [], exclude_serving: [], strikes: [], alternates: [], ...options }];
+}
+
+// Unwrap a Result, failing on Err.
+function ok(result) {
+  assert(!('Err' in result), JSON.stringify(result.Err));
+  return result.Ok;
+}
+
 // Install the global brower compatible fetch.
 global.fetch = fetch;
 
@@ -84,9 +95,16 @@ let secret_key = new Uint8Array(privateKeyDER.slice(7, 7+32));
 const identity = Secp256k1KeyIdentity.fromSecretKey(secret_key);
 const principal = identity.getPrincipal().toText();
 
-// Reinstall to clear all data.
+console.log('dfx ping');
+let ping_output = await exec('dfx ping');
+let root_key_pos = ping_output.stdout.search('"root_key"');
+let root_key = JSON.parse('{ ' + ping_output.stdout.substring(root_key_pos));
+root_key = new Uint8Array(root_key.root_key);
+console.log('root_key', toHex(root_key));
+
+// Reinstall to clear all data, passing the local root key to verify certificates.
 console.log('reinstalling jury canister');
-let reinstall_cmd = '(cd ..;dfx deploy --mode=reinstall -y jury)';
+let reinstall_cmd = '(cd ..;dfx deploy --mode=reinstall -y jury --argument \'(null, opt "' + toHex(root_key) + '")\')';
 console.log('exec:', reinstall_cmd, await exec(reinstall_cmd));
 // Authorize this identity.
 console.log('authorizing principal', principal);
@@ -131,78 +149,148 @@ let juror2 = encoder.encode("juror 2");
 let juror3 = encoder.encode("juror 3");
 let add_jurors = [juror1, juror2, juror3];
 
-let index = await actor.add(add_jurors, encoder.encode("add"));
+let index = ok(await actor.add(add_jurors, encoder.encode("add"), [], [], [], []));
 console.log('add block number', index);
 
 console.log('blockchain length', await actor.length());
 console.log('get pending', await actor.get_pending());
-let block = await actor.get_block(index);
+let block = ok(await actor.get_block(index));
 console.log('get block from index', index, blockToHex(block));
 
-let pool_size = await actor.get_pool_size(index)
+let pool_size = ok(await actor.get_pool_size(index, []))
 console.log("pool size at index", index, pool_size);
-let pool = await actor.get_pool(index, 0, pool_size);
+let pool = ok(await actor.get_pool(index, 0, pool_size, []));
 console.log("pool at index", index, pool.map(uint8ArrayToString));
 
-index = await actor.select(index, 1, encoder.encode("1"));
-let jurors = await actor.get_jurors(index);
+index = ok(await actor.select(index, 1, encoder.encode("1"), []));
+let jurors = ok(await actor.get_jurors(index));
 console.log("select 1", jurors.map(uint8ArrayToString));
+let blinded = ok(await actor.get_block(index)).data.blinded[0];
+console.log("select 1 blinded", blinded.map(toHex));
 
-index = await actor.expand(index, 2, encoder.encode("expand"));
-jurors = await actor.get_jurors(index);
+index = ok(await actor.expand(index, 2, encoder.encode("expand")));
+jurors = ok(await actor.get_jurors(index));
 console.log("expand 2", jurors.map(uint8ArrayToString));
 
-index = await actor.select(index, 2, encoder.encode("2"));
-jurors = await actor.get_jurors(index);
+index = ok(await actor.select(index, 2, encoder.encode("2"), []));
+jurors = ok(await actor.get_jurors(index));
 console.log("select 2", jurors.map(uint8ArrayToString));
 
-index = await actor.select(index, 3, encoder.encode("3"));
-jurors = await actor.get_jurors(index);
+index = ok(await actor.select(index, 3, encoder.encode("3"), []));
+jurors = ok(await actor.get_jurors(index));
 console.log("select 3", jurors.map(uint8ArrayToString));
 
-index = await actor.remove([juror2], encoder.encode("remove"));
+index = ok(await actor.remove([juror2], encoder.encode("remove"), []));
 console.log('remove block number', index);
 console.log('blockchain length', await actor.length());
 console.log('get pending', await actor.get_pending());
-block = await actor.get_block(index);
+block = ok(await actor.get_block(index));
 console.log('get block from index', index, blockToHex(block));
 
-index = await actor.select(index, 1, encoder.encode("1"));
-jurors = await actor.get_jurors(index);
+index = ok(await actor.select(index, 1, encoder.encode("1"), []));
+jurors = ok(await actor.get_jurors(index));
 console.log("select 1", jurors.map(uint8ArrayToString));
-index = await actor.select(index, 2, encoder.encode("2"));
-jurors = await actor.get_jurors(index);
+index = ok(await actor.select(index, 2, encoder.encode("2"), []));
+jurors = ok(await actor.get_jurors(index));
 console.log("select 2", jurors.map(uint8ArrayToString));
 
 let certificate = await actor.get_certificate();
 console.log('certificate', toHex(certificate[0]));
-let result = await actor.commit(certificate[0]);
+let result = ok(await actor.commit(certificate[0]));
 console.log('commit result', result);
 index = result[0];
 
 console.log('blockchain length', await actor.length());
-let size = await actor.get_size(index - 1)
+let size = ok(await actor.get_size(index - 1))
 console.log("jurors size from index", index, size);
 
-pool_size = await actor.get_pool_size(index - 1)
+pool_size = ok(await actor.get_pool_size(index - 1, []))
 console.log("pool size at index", index, pool_size);
 
-pool = await actor.get_pool(index - 1, 0, pool_size);
+pool = ok(await actor.get_pool(index - 1, 0, pool_size, []));
 console.log("pool at index", index, pool.map(uint8ArrayToString));
 
-block = await actor.get_block(index - 1);
+block = ok(await actor.get_block(index - 1));
 console.log('get block from index', index, blockToHex(block));
 
-let found = await actor.find(index - 1, [juror1, juror2, juror3]);
+let blocks = await actor.get_blocks(0, index);
+console.log('get blocks from 0', blocks.blocks.length, 'next', blocks.next);
+
+let found = ok(await actor.find(index - 1, [juror1, juror2, juror3], []));
 console.log('find jurors at index', index, [juror1, juror2, juror3].map(uint8ArrayToString), found[0], found[1], found[2]);
 
-console.log('dfx ping');
-let ping_output = await exec('dfx ping');
-let root_key_pos = ping_output.stdout.search('"root_key"');
-let root_key = JSON.parse('{ ' + ping_output.stdout.substring(root_key_pos));
-root_key = new Uint8Array(root_key.root_key);
-console.log('root_key', toHex(root_key));
+index = ok(await actor.add([juror1], encoder.encode("add to court"), ["court"], [], [], []));
+console.log('add to court block number', index);
+console.log('pools', await actor.get_pools());
+console.log('court pool size', ok(await actor.get_pool_size(index, ["court"])));
+console.log('default pool size', ok(await actor.get_pool_size(index, [])));
+console.log('juror 1 court history', await actor.get_history(juror1, ["court"]));
+
+let secret = new Uint8Array(crypto.randomBytes(32));
+let juror4 = encoder.encode("juror 4");
+index = ok(await actor.add([juror4], encoder.encode("add with secret"), ["court"], [[[secret]]], [], []));
+index = ok(await actor.select(index, 2, encoder.encode("court"), selectOptions({ pool: ["court"] })));
+console.log('court select 2', ok(await actor.get_jurors(index)).map(uint8ArrayToString));
+let rand = new Uint8Array(ok(await actor.get_block(index)).data.rand[0]);
+let token = new Uint8Array(crypto.createHash('sha256').update(secret).update(rand).digest());
+console.log('juror 4 token in jury', ok(await actor.check_token(index, token)));
+
+let juror5 = encoder.encode("juror 5");
+index = ok(await actor.add([juror5], encoder.encode("add weighted"), ["court"], [], [[0]], []));
+index = ok(await actor.select_weighted(index, 2, encoder.encode("weighted"), selectOptions({ pool: ["court"] })));
+console.log('court weighted select 2', ok(await actor.get_jurors(index)).map(uint8ArrayToString));
+
+let juror6 = encoder.encode("juror 6");
+let juror7 = encoder.encode("juror 7");
+index = ok(await actor.add([juror6, juror7], encoder.encode("add categorized"), ["court"], [], [], [["north", "south"]]));
+index = ok(await actor.select_stratified(index, [{ category: "north", count: 1 }, { category: "south", count: 1 }], encoder.encode("stratified"), selectOptions({ pool: ["court"] })));
+console.log('court stratified select', ok(await actor.get_jurors(index)).map(uint8ArrayToString));
+
+index = ok(await actor.select(index, 2, encoder.encode("exclude"), selectOptions({ pool: ["court"], exclude: [[juror1]] })));
+console.log('court select 2 excluding juror 1', ok(await actor.get_jurors(index)).map(uint8ArrayToString));
+index = ok(await actor.expand(index, 1, encoder.encode("expand exclude")));
+console.log('expand 1 excluding juror 1', ok(await actor.get_jurors(index)).map(uint8ArrayToString));
+
+ok(await actor.set_cooldown([{ Blocks: 2 }], ["court"]));
+console.log('court cooldown', ok(await actor.get_cooldown(["court"])));
+index = ok(await actor.select(index, 2, encoder.encode("cooldown"), selectOptions({ pool: ["court"] })));
+console.log('court select 2 during cooldown', ok(await actor.get_jurors(index)).map(uint8ArrayToString));
+ok(await actor.set_cooldown([], ["court"]));
+
+let replaced = ok(await actor.select(index, 2, encoder.encode("to replace"), selectOptions({ pool: ["court"] })));
+let excused = ok(await actor.get_jurors(replaced))[0];
+index = ok(await actor.replace(replaced, [excused], encoder.encode("excused")));
+console.log('replace', uint8ArrayToString(excused), 'with', ok(await actor.get_jurors(index)).map(uint8ArrayToString));
+console.log('court panel', ok(await actor.get_panel(replaced)).map(uint8ArrayToString));
+
+let party = identity.getPrincipal();
+let struck = ok(await actor.select(index, 3, encoder.encode("strikes"), selectOptions({ pool: ["court"], strikes: [1] })));
+let strikes = ok(await actor.get_jurors(struck));
+index = ok(await actor.strike(struck, [strikes[0]], party, encoder.encode("peremptory")));
+console.log('strike', uint8ArrayToString(strikes[0]), 'panel', ok(await actor.get_panel(struck)).map(uint8ArrayToString));
+let over = await actor.strike(struck, [strikes[1]], party, encoder.encode("over the limit"));
+assert('Err' in over && 'StrikeLimit' in over.Err, JSON.stringify(over));
+
+let seated = ok(await actor.select(index, 2, encoder.encode("alternates"), selectOptions({ pool: ["court"], alternates: [2] })));
+let alternates = ok(await actor.get_block(seated)).data.alternates[0];
+console.log('court select 2 with alternates', ok(await actor.get_jurors(seated)).map(uint8ArrayToString), alternates.map(uint8ArrayToString));
+let excusing = ok(await actor.get_jurors(seated))[0];
+index = ok(await actor.promote_alternate(seated, [excusing], encoder.encode("excused")));
+console.log('promote', uint8ArrayToString(ok(await actor.get_jurors(index))[0]), 'for', uint8ArrayToString(excusing));
+index = ok(await actor.promote_alternate(seated, [], encoder.encode("seat")));
+console.log('court panel with alternates', ok(await actor.get_panel(seated)).map(uint8ArrayToString));
+
+index = ok(await actor.add([juror1, juror2, juror3], encoder.encode("add to panel"), ["panel"], [], [], []));
+let serving = ok(await actor.select(index, 1, encoder.encode("panel"), selectOptions({ pool: ["panel"] })));
+index = ok(await actor.select(serving, 2, encoder.encode("serving"), selectOptions({ pool: ["panel"], exclude_serving: [true] })));
+console.log('panel select 2 excluding serving', ok(await actor.get_jurors(index)).map(uint8ArrayToString));
+console.log('serving', ok(await actor.get_block(index)).data.serving[0].map(uint8ArrayToString));
+index = ok(await actor.close(serving, encoder.encode("close")));
+
 let block_certificate = Cbor.decode(block.certificate);
 
+let authorized = await actor.get_authorized();
+console.log('authorized', authorized.map(a => [a.id.toText(), a.roles.map(r => Object.keys(r)[0])]));
+
 console.log('deauthorizing', identity.getPrincipal().toText());
-await actor.deauthorize(identity.getPrincipal());
+ok(await actor.deauthorize(identity.getPrincipal()));
@@ -0,0 +1,84 @@
+// Encoding of blocks by the first version of the canister.
+//
+// The block tree holds sha256 of the Candid encoding of the block data and
+// the previous_hash of a commit is computed from the encoding of the last block
+// of the previous commit, both by the version of the canister at the time.
+// Candid encodes the type along with the value, so the fields and Kind variants
+// added since change the encoding.  Verification therefore also accepts the
+// encoding by the first version of the blocks it could represent.
+
+use crate::block::{Block, Data, Kind};
+use crate::Hash;
+use candid::{CandidType, Encode};
+
+type Blob = Vec<u8>;
+
+#[derive(CandidType)]
+enum Kind0 {
+    Add,
+    Remove,
+    Select,
+    Expand,
+}
+
+#[derive(CandidType)]
+struct Data0 {
+    kind: Kind0,
+    jurors: Vec<Blob>,
+    rand: Option<Blob>,
+    memo: Blob,
+}
+
+#[derive(CandidType)]
+struct Block0 {
+    certificate: Blob,
+    tree: Blob,
+    data: Data0,
+    previous_hash: Hash,
+}
+
+// The data as the first version encoded it, if it has only the fields and
+// kinds of that version.
+fn data0(data: &Data) -> Option<Data0> {
+    let kind = match data.kind {
+        Kind::Add => Kind0::Add,
+        Kind::Remove => Kind0::Remove,
+        Kind::Select => Kind0::Select,
+        Kind::Expand => Kind0::Expand,
+        _ => return None,
+    };
+    let data0 = Data {
+        kind: data.kind.clone(),
+        jurors: data.jurors.clone(),
+        rand: data.rand.clone(),
+        memo: data.memo.clone(),
+        ..Default::default()
+    };
+    if Encode!(&data0).unwrap() != Encode!(data).unwrap() {
+        return None;
+    }
+    Some(Data0 {
+        kind,
+        jurors: data0.jurors,
+        rand: data0.rand,
+        memo: data0.memo,
+    })
+}
+
+// The encoding of the data by the first version, if it could represent it.
+pub fn data_encoding(data: &Data) -> Option<Vec<u8>> {
+    data0(data).map(|data| Encode!(&data).unwrap())
+}
+
+// The encoding of the block by the first version, if it could represent it.
+pub fn block_encoding(block: &Block) -> Option<Vec<u8>> {
+    data0(&block.data).map(|data| {
+        let block = Block0 {
+            certificate: block.certificate.clone(),
+            tree: block.tree.clone(),
+            data,
+            previous_hash: block.previous_hash,
+        };
+        Encode!(&block).unwrap()
+    })
+}
@@ -0,0 +1,319 @@
+// Offline verification of blocks exported from the jury canister.
+//
+// A chain of blocks is valid if, for each block, the certificate is signed by
+// the IC root key (via the subnet delegation), the certified data of the
+// canister is the root of the block tree labeled b"jury_blocks", the tree
+// contains sha256 of the block data at the block index, and the previous_hash
+// links each commit to the last block of the previous commit.  Replaying the
+// chain (see replay.rs) also re-derives the jurors of each selection.
+
+use candid::{Encode, Principal};
+use hash_tree::{HashTree, LookupResult};
+use sha2::Digest;
+use std::fmt;
+#[macro_use]
+extern crate num_derive;
+
+#[path = "../../src/block.rs"]
+pub mod block;
+#[path = "../../src/certificate.rs"]
+pub mod certificate;
+#[path = "../../src/hash_tree.rs"]
+pub mod hash_tree;
+mod legacy;
+#[path = "../../src/pool.rs"]
+pub mod pool;
+pub mod replay;
+#[path = "../../src/selection.rs"]
+pub mod selection;
+
+pub use block::{Block, Data, Kind};
+use certificate::Certificate;
+pub use replay::{replay, Replay, ReplayError};
+
+pub type Hash = [u8; 32];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    // The certificate of the block at index is malformed or not signed.
+    Certificate(u32, String),
+    // The certified data does not match the block tree.
+    CertifiedData(u32),
+    // The block tree is malformed.
+    Tree(u32),
+    // The hash of the block data is not in the block tree.
+    DataHash(u32),
+    // The previous_hash does not link to the previous commit.
+    PreviousHash(u32),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Certificate(i, e) => write!(f, "block {}: {}", i, e),
+            VerifyError::CertifiedData(i) => {
+                write!(f, "block {}: certified data does not match the tree", i)
+            }
+            VerifyError::Tree(i) => write!(f, "block {}: malformed tree", i),
+            VerifyError::DataHash(i) => write!(f, "block {}: data hash not in the tree", i),
+            VerifyError::PreviousHash(i) => write!(f, "block {}: previous hash mismatch", i),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+// The hash of a block, as used for the previous_hash of the next commit.
+pub fn block_hash(block: &Block) -> Hash {
+    hash_encoded_block(&Encode!(block).unwrap())
+}
+
+// The hash of the block data in the block tree.
+pub fn data_hash(data: &Data) -> Hash {
+    sha2::Sha256::digest(Encode!(data).unwrap()).into()
+}
+
+fn hash_encoded_block(encoded_block: &Vec<u8>) -> Hash {
+    sha2::Sha256::digest(Encode!(encoded_block).unwrap()).into()
+}
+
+// The block hash by this and the first version, see legacy.rs.
+fn block_hashes(block: &Block) -> Vec<Hash> {
+    let mut hashes = vec![block_hash(block)];
+    hashes.extend(
+        legacy::block_encoding(block)
+            .as_ref()
+            .map(hash_encoded_block),
+    );
+    hashes
+}
+
+// The data hash by this and the first version, see legacy.rs.
+fn data_hashes(data: &Data) -> Vec<Hash> {
+    let mut hashes = vec![data_hash(data)];
+    hashes.extend(legacy::data_encoding(data).map(|d| -> Hash { sha2::Sha256::digest(d).into() }));
+    hashes
+}
+
+// Verify blocks starting at log index start.  The root key may be DER encoded
+// or the raw 96 byte BLS key.  If previous_hash is given, the first block must
+// link to it (e.g. the hash passed to canister_init on reinstall).
+pub fn verify_blocks(
+    start: u32,
+    blocks: &[Block],
+    root_key: &[u8],
+    canister_id: &Principal,
+    previous_hash: Option<Hash>,
+) -> Result<(), VerifyError> {
+    let canister_id = canister_id.as_slice();
+    let mut previous: Option<&Block> = None;
+    for (i, block) in blocks.iter().enumerate() {
+        let index = start + i as u32;
+        let same_commit =
+            previous.is_some_and(|p| p.certificate == block.certificate && p.tree == block.tree);
+        if !same_commit {
+            verify_commit(index, block, root_key, canister_id)?;
+        }
+        let linked = match previous {
+            Some(p) if same_commit => p.previous_hash == block.previous_hash,
+            Some(p) => block_hashes(p).contains(&block.previous_hash),
+            None => previous_hash.is_none_or(|h| h == block.previous_hash),
+        };
+        if !linked {
+            return Err(VerifyError::PreviousHash(index));
+        }
+        let tree = decode_tree(index, &block.tree)?;
+        let hashes = data_hashes(&block.data);
+        let found = |label: u32| {
+            lookup(&tree, &label.to_be_bytes()).is_some_and(|h| hashes.iter().any(|x| x[..] == *h))
+        };
+        if !found(index) && !found(index + commit_size(&tree)) {
+            return Err(VerifyError::DataHash(index));
+        }
+        if lookup(&tree, b"previous_hash") != Some(&block.previous_hash[..]) {
+            return Err(VerifyError::PreviousHash(index));
+        }
+        previous = Some(block);
+    }
+    Ok(())
+}
+
+// Verify the certificate of the commit containing the block at index.
+fn verify_commit(
+    index: u32,
+    block: &Block,
+    root_key: &[u8],
+    canister_id: &[u8],
+) -> Result<(), VerifyError> {
+    let certificate = Certificate::from_cbor(&block.certificate)
+        .map_err(|e| VerifyError::Certificate(index, e))?;
+    certificate
+        .verify(root_key, canister_id)
+        .map_err(|e| VerifyError::Certificate(index, e))?;
+    let tree = decode_tree(index, &block.tree)?;
+    if certificate.certified_data(canister_id) != Some(tree.digest().to_vec()) {
+        return Err(VerifyError::CertifiedData(index));
+    }
+    Ok(())
+}
+
+fn decode_tree(index: u32, tree: &[u8]) -> Result<HashTree<'static>, VerifyError> {
+    serde_cbor::from_slice(tree).map_err(|_| VerifyError::Tree(index))
+}
+
+// The number of blocks of the commit of the tree.  The first version labeled
+// each block by its log index plus this.
+fn commit_size(tree: &HashTree) -> u32 {
+    tree.list_paths()
+        .iter()
+        .filter(|path| path.len() == 2 && path[1].as_bytes().len() == 4)
+        .count() as u32
+}
+
+fn lookup<'a>(tree: &'a HashTree, label: &[u8]) -> Option<&'a [u8]> {
+    match tree.lookup_path(&["jury_blocks".into(), label.into()]) {
+        LookupResult::Found(value) => Some(value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Decode;
+
+    // Chains recorded from the canister built natively (see the fixtures tests
+    // of the canister), with the canister id, time and randomness fixed, whose
+    // commits are certified by test keys: the first commit by the root key and
+    // the others by a subnet key delegated by it.  The 18 blocks of the chain
+    // are an Authorize, Adds to the default pool (with secrets, weights and
+    // categories) and to the pool "court", then Select (with strikes and
+    // alternates), weighted, stratified and "court" Selects, Expand, Replace,
+    // Strike and Promote blocks, then Close and Remove blocks, a Select
+    // excluding the serving jurors, two Expands and a Close.  The 8 blocks of
+    // the baseline chain were committed by the first version, with its
+    // encoding and tree labels: two Adds and a Select, a Remove, an Expand and
+    // a Select, then an Add and an Expand.
+    pub const CHAIN: &[u8] = include_bytes!("../fixtures/chain.bin");
+    pub const BASELINE_CHAIN: &[u8] = include_bytes!("../fixtures/baseline_chain.bin");
+    const ROOT_KEY: &str = "a4013c73a9cf0cc4e5f5477ec22b1d813fa0200b9d5b034b9fe3855ba36262aa7fc4d804137ddb66582ef0b1953895d3074368544750518fc82f3dfd4bf49dfbd8b5eb6a6b4fbfe7fc8f24ac55bd7d9555d00c6c6cb845298d906aa438eb78ab";
+    const CANISTER_ID: &str = "rrkah-fqaaa-aaaaa-aaaaq-cai";
+
+    pub fn blocks(chain: &[u8]) -> Vec<Block> {
+        Decode!(chain, Vec<Block>).unwrap()
+    }
+
+    fn verify(
+        start: u32,
+        blocks: &[Block],
+        previous_hash: Option<Hash>,
+    ) -> Result<(), VerifyError> {
+        let root_key = hex::decode(ROOT_KEY).unwrap();
+        let canister_id = Principal::from_text(CANISTER_ID).unwrap();
+        verify_blocks(start, blocks, &root_key, &canister_id, previous_hash)
+    }
+
+    #[test]
+    fn verify_chain() {
+        let blocks = blocks(CHAIN);
+        assert_eq!(blocks.len(), 18);
+        verify(0, &blocks, Some([0; 32])).unwrap();
+        // Each block is labeled by its log index.
+        for (index, block) in blocks.iter().enumerate() {
+            let tree = decode_tree(index as u32, &block.tree).unwrap();
+            assert_eq!(
+                lookup(&tree, &(index as u32).to_be_bytes()),
+                Some(&data_hash(&block.data)[..])
+            );
+        }
+    }
+
+    #[test]
+    fn verify_baseline_chain() {
+        let blocks = blocks(BASELINE_CHAIN);
+        assert_eq!(blocks.len(), 8);
+        verify(0, &blocks, Some([0; 32])).unwrap();
+        verify(3, &blocks[3..], Some(blocks[3].previous_hash)).unwrap();
+        // Each block is labeled by its log index plus the size of its commit.
+        let tree = decode_tree(6, &blocks[6].tree).unwrap();
+        assert_eq!(lookup(&tree, &6u32.to_be_bytes()), None);
+        assert!(lookup(&tree, &8u32.to_be_bytes()).is_some());
+        let mut tampered = blocks.clone();
+        tampered[7].data.memo = b"tampered".to_vec();
+        assert_eq!(verify(0, &tampered, None), Err(VerifyError::DataHash(7)));
+    }
+
+    // The tree and the previous_hash of the next commit hold the hashes of the
+    // blocks as the first version encoded them.
+    #[test]
+    fn baseline_encoding() {
+        let blocks = blocks(BASELINE_CHAIN);
+        for (index, block) in blocks.iter().enumerate() {
+            let tree = decode_tree(index as u32, &block.tree).unwrap();
+            let label = (index as u32 + commit_size(&tree)).to_be_bytes();
+            let hash = lookup(&tree, &label).unwrap();
+            assert_ne!(hash, data_hash(&block.data));
+            assert_eq!(data_hashes(&block.data)[1], hash);
+        }
+        assert_ne!(blocks[3].previous_hash, block_hash(&blocks[2]));
+        assert_eq!(block_hashes(&blocks[2])[1], blocks[3].previous_hash);
+        // Fields added since are not in the encoding of the first version, so
+        // blocks with them do not verify against it.
+        let mut added = blocks.clone();
+        added[4].data.pool_index = Some(2);
+        assert_eq!(verify(0, &added, None), Err(VerifyError::DataHash(4)));
+        assert_eq!(data_hashes(&added[4].data).len(), 1);
+    }
+
+    #[test]
+    fn verify_part() {
+        let blocks = blocks(CHAIN);
+        // From the start of the second commit and from within it.
+        verify(3, &blocks[3..], Some(blocks[3].previous_hash)).unwrap();
+        verify(5, &blocks[5..12], None).unwrap();
+        assert_eq!(
+            verify(3, &blocks[3..], Some([0; 32])),
+            Err(VerifyError::PreviousHash(3))
+        );
+        assert_eq!(verify(4, &blocks[3..], None), Err(VerifyError::DataHash(4)));
+    }
+
+    #[test]
+    fn wrong_key() {
+        let blocks = blocks(CHAIN);
+        let canister_id = Principal::from_text(CANISTER_ID).unwrap();
+        let root_key = hex::decode(certificate::IC_ROOT_KEY).unwrap();
+        let error = verify_blocks(0, &blocks, &root_key, &canister_id, None);
+        assert!(matches!(error, Err(VerifyError::Certificate(0, _))));
+        let other = Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 3, 1, 1]);
+        let root_key = hex::decode(ROOT_KEY).unwrap();
+        let error = verify_blocks(0, &blocks, &root_key, &other, None);
+        assert_eq!(error, Err(VerifyError::CertifiedData(0)));
+        // The delegation of the later commits does not cover the canister.
+        let error = verify_blocks(3, &blocks[3..], &root_key, &other, None);
+        assert!(matches!(error, Err(VerifyError::Certificate(3, _))));
+    }
+
+    #[test]
+    fn tampered() {
+        let blocks = blocks(CHAIN);
+        let mut data = blocks.clone();
+        data[4].data.memo = b"tampered".to_vec();
+        assert_eq!(verify(0, &data, None), Err(VerifyError::DataHash(4)));
+        let mut previous_hash = blocks.clone();
+        previous_hash[11].previous_hash[0] ^= 1;
+        assert_eq!(
+            verify(0, &previous_hash, None),
+            Err(VerifyError::PreviousHash(11))
+        );
+        let mut certificate = blocks.clone();
+        certificate[3].certificate = blocks[0].certificate.clone();
+        assert_eq!(
+            verify(0, &certificate, None),
+            Err(VerifyError::CertifiedData(3))
+        );
+        let mut tree = blocks.clone();
+        tree[11].tree = blocks[3].tree.clone();
+        assert_eq!(verify(0, &tree, None), Err(VerifyError::CertifiedData(11)));
+    }
+}
@@ -0,0 +1,326 @@
+// Command line tool to export, verify and inspect a jury chain.
+//
+// The chain file is the Candid encoding of `vec Block` starting at log index 0,
+// built by `import` from the pages returned by the get_blocks query.
+
+use candid::{CandidType, Deserialize, Principal};
+use jury_verifier::certificate::{Certificate, IC_ROOT_KEY};
+use jury_verifier::selection::{find_token, token};
+use jury_verifier::{replay, verify_blocks, Block, Hash, Replay};
+use std::process::exit;
+
+const USAGE: &str = "usage: jury-verifier <command> <chain file> [args]
+
+commands:
+  import <page file>...    write the chain file from get_blocks replies from index 0 in order,
+                           e.g. from `dfx canister call --output raw` (hex or binary)
+  next-page                print the start of the next page of the get_blocks reply in the
+                           file (given in place of the chain file), if any
+  verify --canister-id <id> [--root-key <hex>] [--previous-hash <hex>]
+      verify certificates, the block tree and previous hashes, then replay
+  show-block <index>       print a block
+  dump                     print every block
+  pool-at <index> [pool]   print the juror pool at a log index
+  history <juror hex> [pool]
+                           print the log indexes where a juror was added/removed
+  authorized-at <index>    print the principals and roles authorized by blocks up to index
+  check-secret <index> <secret hex>
+                           print the token of the juror with the secret on the jury at index
+  check-token <index> <token hex>
+                           check that the juror with the token is on the jury at index
+  panel <index>            print the current panel of the jury of the block at index
+  replay                   re-derive the jurors of each Select/Expand/Replace block";
+
+// A page of blocks returned by the get_blocks query.
+#[derive(CandidType, Deserialize)]
+struct Blocks {
+    blocks: Vec<Block>,
+    next: Option<u32>,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() < 2 {
+        fail(USAGE);
+    }
+    let rest = &args[2..];
+    match args[0].as_str() {
+        "import" => return import(&args[1], rest),
+        "next-page" => {
+            if let Some(next) = read_page(&args[1]).next {
+                println!("{}", next);
+            }
+            return;
+        }
+        _ => (),
+    }
+    let blocks = read_chain(&args[1]);
+    match args[0].as_str() {
+        "verify" => verify(&blocks, rest),
+        "show-block" => show_block(&blocks, parse_index(rest)),
+        "dump" => {
+            for index in 0..blocks.len() {
+                if index > 0 {
+                    println!();
+                }
+                show_block(&blocks, index as u32);
+            }
+        }
+        "pool-at" => {
+            let index = parse_index(rest);
+            let replay = replay_chain(&blocks);
+            if let Some(pool) = replay.pool(pool_arg(&rest[1..])) {
+                for juror in pool.range(index, 0, pool.size(index)) {
+                    println!("{}", hex::encode(juror));
+                }
+            }
+        }
+        "history" => {
+            let juror = rest.first().unwrap_or_else(|| fail(USAGE));
+            let juror = hex::decode(juror).unwrap_or_else(|_| fail("juror must be hex"));
+            let replay = replay_chain(&blocks);
+            println!("{:?}", replay.history(pool_arg(&rest[1..]), &juror));
+        }
+        "authorized-at" => {
+            let index = parse_index(rest) as usize;
+            if index >= blocks.len() {
+                fail("index out of range");
+            }
+            let replay = replay_chain(&blocks[..=index]);
+            for (principal, roles) in &replay.authorized {
+                println!("{} {:?}", principal, roles);
+            }
+        }
+        "check-secret" | "check-token" => {
+            let index = parse_index(rest);
+            let arg = rest.get(1).unwrap_or_else(|| fail(USAGE));
+            let arg = hex::decode(arg).unwrap_or_else(|_| fail("argument must be hex"));
+            let block = blocks
+                .get(index as usize)
+                .unwrap_or_else(|| fail("index out of range"));
+            let (Some(rand), Some(blinded)) = (&block.data.rand, &block.data.blinded) else {
+                fail("block has no blinded ids");
+            };
+            let token = match args[0].as_str() {
+                "check-secret" => token(&arg, rand),
+                _ => arg,
+            };
+            if !find_token(blinded, &token) {
+                fail("not in the jury");
+            }
+            println!("{}", hex::encode(token));
+        }
+        "panel" => {
+            let index = parse_index(rest);
+            let replay = replay_chain(&blocks);
+            let panel = replay
+                .panel(index)
+                .unwrap_or_else(|| fail("block is not a selection"));
+            for juror in panel {
+                println!("{}", hex::encode(juror));
+            }
+        }
+        "replay" => {
+            replay_chain(&blocks);
+        }
+        _ => fail(USAGE),
+    }
+}
+
+// Write the chain from pages of get_blocks replies starting at index 0, each
+// starting at the next of the one before.
+fn import(path: &str, pages: &[String]) {
+    if pages.is_empty() {
+        fail(USAGE);
+    }
+    let mut blocks = Vec::new();
+    let mut next = None;
+    for (i, page) in pages.iter().enumerate() {
+        let page = read_page(page);
+        blocks.extend(page.blocks);
+        let last = i + 1 == pages.len();
+        if page
+            .next
+            .map_or(!last, |next| next as usize != blocks.len())
+        {
+            fail("pages are not consecutive");
+        }
+        next = page.next;
+    }
+    let bytes = candid::encode_one(&blocks).unwrap();
+    std::fs::write(path, bytes).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    match next {
+        Some(next) => eprintln!("imported {} blocks, more from {}", blocks.len(), next),
+        None => eprintln!("imported {} blocks", blocks.len()),
+    }
+}
+
+// A get_blocks reply, Candid encoded or hex as printed by dfx --output raw.
+fn read_page(path: &str) -> Blocks {
+    let bytes = std::fs::read(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    let bytes = match bytes.starts_with(b"DIDL") {
+        true => bytes,
+        false => hex::decode(String::from_utf8_lossy(&bytes).trim())
+            .unwrap_or_else(|_| fail(&format!("{}: not Candid or hex", path))),
+    };
+    candid::decode_one(&bytes).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)))
+}
+
+fn verify(blocks: &[Block], args: &[String]) {
+    let mut canister_id = None;
+    let mut root_key = hex::decode(IC_ROOT_KEY).unwrap();
+    let mut previous_hash: Option<Hash> = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| fail(USAGE));
+        match arg.as_str() {
+            "--canister-id" => {
+                canister_id = Some(
+                    Principal::from_text(value).unwrap_or_else(|_| fail("invalid canister id")),
+                )
+            }
+            "--root-key" => {
+                root_key = hex::decode(value).unwrap_or_else(|_| fail("root key must be hex"))
+            }
+            "--previous-hash" => {
+                let hash = hex::decode(value).ok().and_then(|h| h.try_into().ok());
+                previous_hash = Some(hash.unwrap_or_else(|| fail("invalid previous hash")));
+            }
+            _ => fail(USAGE),
+        }
+    }
+    let canister_id = canister_id.unwrap_or_else(|| fail(USAGE));
+    if let Err(e) = verify_blocks(0, blocks, &root_key, &canister_id, previous_hash) {
+        fail(&e.to_string());
+    }
+    println!("verified {} blocks", blocks.len());
+    replay_chain(blocks);
+}
+
+fn show_block(blocks: &[Block], index: u32) {
+    let block = blocks
+        .get(index as usize)
+        .unwrap_or_else(|| fail("index out of range"));
+    let data = &block.data;
+    println!("index: {}", index);
+    println!("kind: {:?}", data.kind);
+    println!("memo: {}", String::from_utf8_lossy(&data.memo));
+    if let Some(rand) = &data.rand {
+        println!("rand: {}", hex::encode(rand));
+    }
+    if let Some(algorithm) = data.algorithm {
+        println!("algorithm: {}", algorithm);
+    }
+    if let Some(pool_index) = data.pool_index {
+        println!("pool_index: {}", pool_index);
+    }
+    if let Some(jurors_index) = data.jurors_index {
+        println!("jurors_index: {}", jurors_index);
+    }
+    if let Some(principal) = data.principal {
+        println!("principal: {}", principal);
+    }
+    if let Some(roles) = &data.roles {
+        println!("roles: {:?}", roles);
+    }
+    if let Some(pool) = &data.pool {
+        println!("pool: {}", pool);
+    }
+    println!("jurors:");
+    for juror in &data.jurors {
+        println!("  {}", hex::encode(juror));
+    }
+    if let Some(alternates) = &data.alternates {
+        println!("alternates:");
+        for juror in alternates {
+            println!("  {}", hex::encode(juror));
+        }
+    }
+    if let Some(jurors_out) = &data.jurors_out {
+        println!("jurors_out:");
+        for juror in jurors_out {
+            println!("  {}", hex::encode(juror));
+        }
+    }
+    if let Some(weights) = &data.weights {
+        println!("weights: {:?}", weights);
+    }
+    if let Some(categories) = &data.categories {
+        println!("categories: {:?}", categories);
+    }
+    if let Some(quotas) = &data.quotas {
+        for quota in quotas {
+            println!("quota: {} {}", quota.category, quota.count);
+        }
+    }
+    if let Some(strikes) = data.strikes {
+        println!("strikes: {}", strikes);
+    }
+    if let Some(cooldown) = &data.cooldown {
+        println!("cooldown: {:?}", cooldown);
+    }
+    if let Some(time) = data.time {
+        println!("time: {}", time);
+    }
+    if let Some(serving) = &data.serving {
+        println!("serving:");
+        for juror in serving {
+            println!("  {}", hex::encode(juror));
+        }
+    }
+    if let Some(exclude) = &data.exclude {
+        println!("exclude:");
+        for juror in exclude {
+            println!("  {}", hex::encode(juror));
+        }
+    }
+    if let Some(commitments) = &data.commitments {
+        println!("commitments:");
+        for c in commitments {
+            println!("  {}", c.as_ref().map_or("none".to_string(), hex::encode));
+        }
+    }
+    if let Some(blinded) = &data.blinded {
+        println!("blinded:");
+        for id in blinded {
+            println!("  {}", hex::encode(id));
+        }
+    }
+    println!("previous_hash: {}", hex::encode(block.previous_hash));
+    if let Some(time) = Certificate::from_cbor(&block.certificate)
+        .ok()
+        .and_then(|c| c.time())
+    {
+        println!("certificate time: {}", time);
+    }
+}
+
+fn replay_chain(blocks: &[Block]) -> Replay {
+    let replay = replay(blocks).unwrap_or_else(|e| fail(&e.to_string()));
+    if !replay.unverified.is_empty() {
+        eprintln!("unable to re-derive blocks: {:?}", replay.unverified);
+    }
+    eprintln!("replayed {} blocks", replay.length());
+    replay
+}
+
+fn read_chain(path: &str) -> Vec<Block> {
+    let bytes = std::fs::read(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    candid::decode_one(&bytes).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)))
+}
+
+fn parse_index(args: &[String]) -> u32 {
+    args.first()
+        .and_then(|a| a.parse().ok())
+        .unwrap_or_else(|| fail(USAGE))
+}
+
+// The optional pool argument, "" for the default pool.
+fn pool_arg(args: &[String]) -> &str {
+    args.first().map_or("", |p| p.as_str())
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    exit(1)
+}
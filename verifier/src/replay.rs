@@ -0,0 +1,769 @@
+// Re-execution of a chain of blocks from log index 0, re-deriving the jurors of
+// each Select, Expand and Replace block from its pool reconstructed from the Add and
+// Remove blocks before it, using the same code as the canister.
+
+use crate::block::{Block, Cooldown, Data, Kind, Role};
+use crate::pool::Pool;
+use crate::selection::{self, cooling_down, make_jury, make_stratified_jury, STRATIFIED_ALGORITHM};
+use crate::Hash;
+use candid::Principal;
+use std::collections::BTreeMap;
+use std::fmt;
+
+type Blob = Vec<u8>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    // The block at index is missing rand or principal or has a malformed one.
+    Malformed(u32),
+    // The jurors of the block at index are not those re-derived from the pool.
+    Jurors(u32),
+    // The blinded ids of the block at index are not those of its jurors.
+    Blinded(u32),
+    // The serving jurors of the block at index are not those of the open juries.
+    Serving(u32),
+    // The block at index extends or closes a jury which is not open.
+    Closed(u32),
+    // The block at index replaces or strikes jurors which are not on the panel
+    // of the jury.
+    Panel(u32),
+    // The Strike block at index exceeds the strikes of the party on the jury.
+    Strikes(u32),
+    // The Promote block at index does not seat the next alternate of the jury.
+    Alternate(u32),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Malformed(i) => write!(f, "block {}: malformed selection", i),
+            ReplayError::Jurors(i) => write!(f, "block {}: jurors do not match the pool", i),
+            ReplayError::Blinded(i) => {
+                write!(f, "block {}: blinded ids do not match the jurors", i)
+            }
+            ReplayError::Serving(i) => {
+                write!(
+                    f,
+                    "block {}: serving jurors do not match the open juries",
+                    i
+                )
+            }
+            ReplayError::Closed(i) => write!(f, "block {}: the jury is not open", i),
+            ReplayError::Panel(i) => write!(f, "block {}: jurors are not on the panel", i),
+            ReplayError::Strikes(i) => {
+                write!(f, "block {}: the party has no strikes left", i)
+            }
+            ReplayError::Alternate(i) => {
+                write!(f, "block {}: not the next alternate of the jury", i)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+// A Select, Expand or Replace block.
+struct Draw {
+    pool: Option<String>,
+    seed: Hash,
+    algorithm: Option<u32>,
+    pool_index: Option<u32>,
+    exclude: Option<Vec<Blob>>,
+    cooldown: Option<Cooldown>,
+    // The jurors excluded from the pool: those listed and those cooling down.
+    excluded: Vec<Blob>,
+    time: Option<u64>,
+    serving: Option<Vec<Blob>>,
+    jurors: Vec<Blob>,
+    jurors_out: Option<Vec<Blob>>,
+    // The alternates of a Select block.
+    alternates: Vec<Blob>,
+    count: u32,
+    // The log index of the Select block of the jury.
+    select: u32,
+}
+
+#[derive(Default)]
+pub struct Replay {
+    // Map from pool id ("" for the default pool) to pool.
+    pub pools: BTreeMap<String, Pool>,
+    // Map from (pool id, category) to the pool of the jurors of the category.
+    strata: BTreeMap<(String, String), Pool>,
+    // Map from (pool, juror) to history: add index, (delete index, (add index ...))
+    histories: BTreeMap<(String, Blob), Vec<u32>>,
+    // Map from (pool, juror, add index) to the commitment the juror was added with.
+    commitments: BTreeMap<(String, Blob, u32), Blob>,
+    // Map from (pool, juror, add index) to the category the juror was added with.
+    categories: BTreeMap<(String, Blob, u32), String>,
+    draws: BTreeMap<u32, Draw>,
+    // Map from the Select index of each open jury to the data of its blocks.
+    juries: BTreeMap<u32, Vec<Data>>,
+    // The same for closed juries.
+    closed: BTreeMap<u32, Vec<Data>>,
+    // Select blocks which predate pool_index and so can not be re-derived.
+    pub unverified: Vec<u32>,
+    // The roles of principals authorized by Authorize blocks.  Controllers are
+    // not included.
+    pub authorized: BTreeMap<Principal, Vec<Role>>,
+    length: u32,
+}
+
+// Replay blocks from log index 0.
+pub fn replay(blocks: &[Block]) -> Result<Replay, ReplayError> {
+    let mut replay = Replay::default();
+    for block in blocks {
+        replay.apply(&block.data)?;
+    }
+    Ok(replay)
+}
+
+impl Replay {
+    // Number of blocks replayed.
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    pub fn history(&self, pool: &str, juror: &[u8]) -> Vec<u32> {
+        self.histories
+            .get(&(pool.to_string(), juror.to_vec()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // The pool, or None if nothing was ever added to it.
+    pub fn pool(&self, pool: &str) -> Option<&Pool> {
+        self.pools.get(pool)
+    }
+
+    // Apply the next block.
+    pub fn apply(&mut self, data: &Data) -> Result<(), ReplayError> {
+        let index = self.length;
+        let pool = data.pool.clone().unwrap_or_default();
+        match data.kind {
+            Kind::Add => {
+                for (i, j) in data.jurors.iter().enumerate() {
+                    let key = (pool.clone(), j.clone());
+                    let history = self.histories.entry(key).or_default();
+                    if history.len() % 2 == 1 {
+                        continue;
+                    }
+                    history.push(index);
+                    let weight = data.weights.as_ref().and_then(|w| w.get(i));
+                    let weight = weight.copied().unwrap_or(1);
+                    self.pools
+                        .entry(pool.clone())
+                        .or_default()
+                        .insert(index, j, weight);
+                    let key = (pool.clone(), j.clone(), index);
+                    let commitment = data.commitments.as_ref().and_then(|c| c.get(i));
+                    if let Some(Some(c)) = commitment {
+                        self.commitments.insert(key.clone(), c.clone());
+                    }
+                    let category = data.categories.as_ref().and_then(|c| c.get(i));
+                    let category = category.cloned().unwrap_or_default();
+                    let stratum = self.strata.entry((pool.clone(), category.clone()));
+                    stratum.or_default().insert(index, j, weight);
+                    self.categories.insert(key, category);
+                }
+            }
+            Kind::Remove => {
+                for j in &data.jurors {
+                    let key = (pool.clone(), j.clone());
+                    let Some(history) = self.histories.get_mut(&key) else {
+                        continue;
+                    };
+                    if history.len() % 2 == 0 {
+                        continue;
+                    }
+                    let category = self.category(&pool, j, index);
+                    self.histories.get_mut(&key).unwrap().push(index);
+                    self.pools.get_mut(&pool).unwrap().remove(index, j);
+                    let stratum = self.strata.get_mut(&(pool.clone(), category)).unwrap();
+                    stratum.remove(index, j);
+                }
+            }
+            Kind::Select => self.select(index, data)?,
+            Kind::Expand => self.expand(index, data)?,
+            Kind::Replace => self.replace(index, data)?,
+            Kind::Strike => self.strike(index, data)?,
+            Kind::Promote => self.promote(index, data)?,
+            Kind::Authorize => {
+                let principal = data.principal.ok_or(ReplayError::Malformed(index))?;
+                let roles = data.roles.as_ref().ok_or(ReplayError::Malformed(index))?;
+                if roles.is_empty() {
+                    self.authorized.remove(&principal);
+                } else {
+                    self.authorized.insert(principal, roles.clone());
+                }
+            }
+            Kind::Deauthorize => {
+                let principal = data.principal.ok_or(ReplayError::Malformed(index))?;
+                self.authorized.remove(&principal);
+            }
+            Kind::Close => {
+                let select = data.jurors_index.ok_or(ReplayError::Malformed(index))?;
+                let jury = self
+                    .juries
+                    .remove(&select)
+                    .ok_or(ReplayError::Closed(index))?;
+                self.closed.insert(select, jury);
+            }
+        }
+        self.length += 1;
+        Ok(())
+    }
+
+    fn select(&mut self, index: u32, data: &Data) -> Result<(), ReplayError> {
+        let draw = Draw {
+            pool: data.pool.clone(),
+            seed: seed(index, data)?,
+            algorithm: data.algorithm,
+            pool_index: data.pool_index,
+            exclude: data.exclude.clone(),
+            cooldown: data.cooldown,
+            excluded: self.excluded(index, data)?,
+            time: data.time,
+            serving: data.serving.clone(),
+            jurors: data.jurors.clone(),
+            jurors_out: None,
+            alternates: data.alternates.clone().unwrap_or_default(),
+            count: data.jurors.len() as u32,
+            select: index,
+        };
+        // The alternates are drawn after the jurors.
+        let drawn: Vec<Blob> = draw
+            .jurors
+            .iter()
+            .chain(&draw.alternates)
+            .cloned()
+            .collect();
+        match data.pool_index {
+            None => self.unverified.push(index),
+            Some(pool_index) => {
+                let jurors = if draw.algorithm == Some(STRATIFIED_ALGORITHM) {
+                    let quotas = data.quotas.as_ref().ok_or(ReplayError::Malformed(index))?;
+                    let empty = Pool::new();
+                    let pool_id = draw.pool.clone().unwrap_or_default();
+                    let strata: Vec<&Pool> = quotas
+                        .iter()
+                        .map(|q| {
+                            let key = (pool_id.clone(), q.category.clone());
+                            self.strata.get(&key).unwrap_or(&empty)
+                        })
+                        .collect();
+                    let excluded = &draw.excluded;
+                    make_stratified_jury(&strata, pool_index, draw.seed, quotas, excluded)
+                } else {
+                    self.make_jury(
+                        &draw.pool,
+                        pool_index,
+                        drawn.len() as u32,
+                        draw.seed,
+                        draw.algorithm,
+                        &draw.excluded,
+                    )
+                };
+                if jurors.as_ref() != Some(&drawn) {
+                    return Err(ReplayError::Jurors(index));
+                }
+            }
+        }
+        self.check_blinded(index, data, data.pool_index.unwrap_or(index))?;
+        self.draws.insert(index, draw);
+        // The first version, which did not record pool_index, could not close
+        // its juries, so they are closed.
+        match data.pool_index {
+            Some(_) => self.juries.insert(index, vec![data.clone()]),
+            None => self.closed.insert(index, vec![data.clone()]),
+        };
+        Ok(())
+    }
+
+    fn expand(&mut self, index: u32, data: &Data) -> Result<(), ReplayError> {
+        let seed = seed(index, data)?;
+        // Blocks without jurors_index may extend any earlier draw with the same seed.
+        let candidates: Vec<u32> = match data.jurors_index {
+            Some(jurors_index) => vec![jurors_index],
+            None => {
+                let mut candidates: Vec<u32> = self
+                    .draws
+                    .iter()
+                    .filter(|(_, d)| d.seed == seed && d.jurors_out.is_none())
+                    .map(|(i, _)| *i)
+                    .collect();
+                candidates.sort_unstable_by(|a, b| b.cmp(a));
+                candidates
+            }
+        };
+        let count = data.jurors.len() as u32;
+        for jurors_index in candidates {
+            let Some(old) = self.draws.get(&jurors_index) else {
+                continue;
+            };
+            if old.jurors_out.is_some()
+                || old.seed != seed
+                || old.algorithm != data.algorithm
+                || old.pool_index != data.pool_index
+                || old.pool != data.pool
+                || old.exclude != data.exclude
+                || old.cooldown != data.cooldown
+                || old.serving != data.serving
+            {
+                continue;
+            }
+            let pool_index = old.pool_index.unwrap_or(jurors_index);
+            // The expansion follows all the jurors drawn for the jury, except
+            // for blocks without jurors_index which followed the jurors of the
+            // block they extend.
+            let old_count = match data.jurors_index {
+                Some(_) => selection::panel(self.jury(old.select).unwrap_or_default()).1,
+                None => old.count,
+            };
+            let jurors = self.make_jury(
+                &old.pool,
+                pool_index,
+                old_count + count,
+                seed,
+                old.algorithm,
+                &old.excluded,
+            );
+            if jurors.is_some_and(|j| j[old_count as usize..] == data.jurors[..]) {
+                self.check_blinded(index, data, pool_index)?;
+                let select = old.select;
+                // Blocks without jurors_index extend the closed juries of the
+                // first version.
+                let juries = match data.jurors_index {
+                    Some(_) => &mut self.juries,
+                    None => &mut self.closed,
+                };
+                juries
+                    .get_mut(&select)
+                    .ok_or(ReplayError::Closed(index))?
+                    .push(data.clone());
+                let draw = Draw {
+                    pool: data.pool.clone(),
+                    seed,
+                    algorithm: data.algorithm,
+                    pool_index: data.pool_index,
+                    exclude: data.exclude.clone(),
+                    cooldown: data.cooldown,
+                    excluded: old.excluded.clone(),
+                    time: data.time,
+                    serving: data.serving.clone(),
+                    jurors: data.jurors.clone(),
+                    jurors_out: None,
+                    alternates: Vec::new(),
+                    count,
+                    select,
+                };
+                self.draws.insert(index, draw);
+                return Ok(());
+            }
+        }
+        Err(ReplayError::Jurors(index))
+    }
+
+    fn replace(&mut self, index: u32, data: &Data) -> Result<(), ReplayError> {
+        let seed = seed(index, data)?;
+        let select = data.jurors_index.ok_or(ReplayError::Malformed(index))?;
+        let jurors_out = data
+            .jurors_out
+            .as_ref()
+            .ok_or(ReplayError::Malformed(index))?;
+        let jury = self.juries.get(&select).ok_or(ReplayError::Closed(index))?;
+        let old = &self.draws[&select];
+        if old.seed != seed
+            || old.algorithm != data.algorithm
+            || old.algorithm == Some(STRATIFIED_ALGORITHM)
+            || old.pool_index != data.pool_index
+            || old.pool != data.pool
+            || old.exclude != data.exclude
+            || old.cooldown != data.cooldown
+            || old.serving != data.serving
+            || jurors_out.len() != data.jurors.len()
+        {
+            return Err(ReplayError::Jurors(index));
+        }
+        let (panel, drawn) = selection::panel(jury);
+        for (i, juror) in jurors_out.iter().enumerate() {
+            if !panel.contains(juror) || jurors_out[..i].contains(juror) {
+                return Err(ReplayError::Panel(index));
+            }
+        }
+        let count = data.jurors.len() as u32;
+        let pool_index = old.pool_index.unwrap_or(select);
+        let jurors = self.make_jury(
+            &old.pool,
+            pool_index,
+            drawn + count,
+            seed,
+            old.algorithm,
+            &old.excluded,
+        );
+        if jurors.as_ref().map(|j| &j[drawn as usize..]) != Some(&data.jurors[..]) {
+            return Err(ReplayError::Jurors(index));
+        }
+        self.check_blinded(index, data, pool_index)?;
+        let draw = Draw {
+            pool: data.pool.clone(),
+            seed,
+            algorithm: data.algorithm,
+            pool_index: data.pool_index,
+            exclude: data.exclude.clone(),
+            cooldown: data.cooldown,
+            excluded: old.excluded.clone(),
+            time: data.time,
+            serving: data.serving.clone(),
+            jurors: data.jurors.clone(),
+            jurors_out: data.jurors_out.clone(),
+            alternates: Vec::new(),
+            count,
+            select,
+        };
+        self.juries.get_mut(&select).unwrap().push(data.clone());
+        self.draws.insert(index, draw);
+        Ok(())
+    }
+
+    fn strike(&mut self, index: u32, data: &Data) -> Result<(), ReplayError> {
+        let select = data.jurors_index.ok_or(ReplayError::Malformed(index))?;
+        let party = data.principal.ok_or(ReplayError::Malformed(index))?;
+        let jury = self.juries.get(&select).ok_or(ReplayError::Closed(index))?;
+        let (panel, _) = selection::panel(jury);
+        for (i, juror) in data.jurors.iter().enumerate() {
+            if !panel.contains(juror) || data.jurors[..i].contains(juror) {
+                return Err(ReplayError::Panel(index));
+            }
+        }
+        let struck: usize = jury
+            .iter()
+            .filter(|d| matches!(d.kind, Kind::Strike) && d.principal == Some(party))
+            .map(|d| d.jurors.len())
+            .sum();
+        let limit = jury[0].strikes.unwrap_or(0);
+        if struck + data.jurors.len() > limit as usize {
+            return Err(ReplayError::Strikes(index));
+        }
+        self.juries.get_mut(&select).unwrap().push(data.clone());
+        Ok(())
+    }
+
+    fn promote(&mut self, index: u32, data: &Data) -> Result<(), ReplayError> {
+        let select = data.jurors_index.ok_or(ReplayError::Malformed(index))?;
+        let [alternate] = &data.jurors[..] else {
+            return Err(ReplayError::Malformed(index));
+        };
+        let juror_out = match data.jurors_out.as_deref() {
+            None => None,
+            Some([juror]) => Some(juror),
+            Some(_) => return Err(ReplayError::Malformed(index)),
+        };
+        let jury = self.juries.get(&select).ok_or(ReplayError::Closed(index))?;
+        if juror_out.is_some_and(|j| !selection::panel(jury).0.contains(j)) {
+            return Err(ReplayError::Panel(index));
+        }
+        if selection::alternates(jury).first() != Some(alternate) {
+            return Err(ReplayError::Alternate(index));
+        }
+        self.juries.get_mut(&select).unwrap().push(data.clone());
+        Ok(())
+    }
+
+    // The current panel of the jury of the Select, Expand or Replace block at
+    // index, or None if there is no such block.
+    pub fn panel(&self, index: u32) -> Option<Vec<Blob>> {
+        let jury = self.jury(self.draws.get(&index)?.select)?;
+        Some(selection::panel(jury).0)
+    }
+
+    // The data of the blocks of the open or closed jury of the Select block.
+    fn jury(&self, select: u32) -> Option<&[Data]> {
+        self.juries
+            .get(&select)
+            .or_else(|| self.closed.get(&select))
+            .map(Vec::as_slice)
+    }
+
+    // The jurors excluded from a Select block: those listed, those serving on
+    // the open juries of its pool and those drawn from its pool by the Select,
+    // Expand and Replace blocks within its cooldown.
+    fn excluded(&self, index: u32, data: &Data) -> Result<Vec<Blob>, ReplayError> {
+        if let Some(serving) = &data.serving {
+            if *serving != self.serving(&data.pool) {
+                return Err(ReplayError::Serving(index));
+            }
+        }
+        let mut cooling = Vec::new();
+        if let Some(cooldown) = &data.cooldown {
+            let now = data.time.ok_or(ReplayError::Malformed(index))?;
+            let draws = self.draws.range(..index).rev();
+            for (at, draw) in draws.filter(|(_, d)| d.pool == data.pool) {
+                if !cooling_down(cooldown, index, now, *at, draw.time) {
+                    break;
+                }
+                cooling.extend(draw.jurors.iter().cloned());
+                cooling.extend(draw.alternates.iter().cloned());
+            }
+        }
+        Ok(selection::excluded(data, cooling))
+    }
+
+    // The jurors on the panels of the open juries of the pool and their
+    // alternates not yet seated, in the order they were drawn, each once.
+    fn serving(&self, pool: &Option<String>) -> Vec<Blob> {
+        selection::serving(self.juries.values().filter(|jury| jury[0].pool == *pool))
+    }
+
+    // The (pool, juror, add index) of the Add of a juror in the pool at index, if any.
+    fn added_key(&self, pool: &str, juror: &[u8], index: u32) -> Option<(String, Blob, u32)> {
+        let history = self.history(pool, juror);
+        let changes = history.iter().filter(|i| **i <= index).count();
+        if changes % 2 == 0 {
+            return None;
+        }
+        Some((pool.to_string(), juror.to_vec(), history[changes - 1]))
+    }
+
+    // The commitment of a juror in the pool at index, if it was added with one.
+    fn commitment(&self, pool: &str, juror: &[u8], index: u32) -> Option<&Blob> {
+        self.commitments.get(&self.added_key(pool, juror, index)?)
+    }
+
+    // The category of a juror in the pool at index, "" if it was added without one.
+    fn category(&self, pool: &str, juror: &[u8], index: u32) -> String {
+        self.added_key(pool, juror, index)
+            .and_then(|key| self.categories.get(&key).cloned())
+            .unwrap_or_default()
+    }
+
+    // The ids depend on the secrets of the jurors, which are not in the chain, so
+    // only check that they are sorted and that there is one for each juror or
+    // alternate with a commitment.  Blocks before blinded ids were recorded have
+    // none.
+    fn check_blinded(&self, index: u32, data: &Data, pool_index: u32) -> Result<(), ReplayError> {
+        let Some(blinded) = &data.blinded else {
+            return Ok(());
+        };
+        let pool = data.pool.as_deref().unwrap_or_default();
+        let committed = data
+            .jurors
+            .iter()
+            .chain(data.alternates.iter().flatten())
+            .filter(|j| self.commitment(pool, j, pool_index).is_some())
+            .count();
+        let sorted = blinded.windows(2).all(|w| w[0] < w[1]);
+        if !sorted || blinded.len() != committed {
+            return Err(ReplayError::Blinded(index));
+        }
+        Ok(())
+    }
+
+    fn make_jury(
+        &self,
+        pool: &Option<String>,
+        index: u32,
+        count: u32,
+        seed: Hash,
+        algorithm: Option<u32>,
+        exclude: &[Blob],
+    ) -> Option<Vec<Blob>> {
+        let empty = Pool::new();
+        let pool = self
+            .pool(pool.as_deref().unwrap_or_default())
+            .unwrap_or(&empty);
+        make_jury(pool, index, count, seed, algorithm, exclude)
+    }
+}
+
+fn seed(index: u32, data: &Data) -> Result<Hash, ReplayError> {
+    data.rand
+        .as_ref()
+        .and_then(|r| r.as_slice().try_into().ok())
+        .ok_or(ReplayError::Malformed(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::{find_token, token};
+    use crate::tests::{blocks, BASELINE_CHAIN, CHAIN};
+
+    fn jurors(hex: &[&str]) -> Vec<Blob> {
+        hex.iter().map(|j| hex::decode(j).unwrap()).collect()
+    }
+
+    // The error replaying the chain with the data of the block at index tampered.
+    fn tampered(index: usize, tamper: impl Fn(&mut Data)) -> Option<ReplayError> {
+        let mut blocks = blocks(CHAIN);
+        tamper(&mut blocks[index].data);
+        replay(&blocks).err()
+    }
+
+    // Put another juror of the pool first.
+    fn other(jurors: &mut [Blob]) {
+        jurors[0] = vec![b'j', 0x0a];
+    }
+
+    // The replay agrees with get_pool of the canister which recorded the chain.
+    #[test]
+    fn agrees_with_canister() {
+        let replayed = replay(&blocks(CHAIN)).unwrap();
+        assert_eq!(replayed.length(), 18);
+        let pool = replayed.pool("").unwrap();
+        let expected: Vec<Blob> = (0..24u8)
+            .filter(|i| ![5, 6].contains(i))
+            .map(|i| vec![b'j', i])
+            .collect();
+        assert_eq!(pool.range(17, 0, u32::MAX), expected);
+        assert_eq!(pool.size(12), 24);
+        assert_eq!(replayed.history("", &[b'j', 5]), [1, 13]);
+        assert_eq!(replayed.pool("court").unwrap().size(17), 8);
+    }
+
+    // The Selects of the first version do not record the index of their pool,
+    // but its Expands drew from the pool at the index of the Select.
+    #[test]
+    fn baseline() {
+        let mut blocks = blocks(BASELINE_CHAIN);
+        let replayed = replay(&blocks).unwrap();
+        assert_eq!(replayed.unverified, [2, 5]);
+        assert_eq!(replayed.pool("").unwrap().size(7), 15);
+        // Their juries are closed.
+        let mut closed = blocks.clone();
+        closed.push(Block {
+            data: Data {
+                kind: Kind::Close,
+                jurors_index: Some(5),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert_eq!(replay(&closed).err(), Some(ReplayError::Closed(8)));
+        blocks[7].data.jurors = vec![vec![b'j', 5]];
+        assert_eq!(replay(&blocks).err(), Some(ReplayError::Jurors(7)));
+    }
+
+    #[test]
+    fn jurors_tampered() {
+        for index in [3, 7, 15] {
+            assert_eq!(
+                tampered(index, |d| other(&mut d.jurors)),
+                Some(ReplayError::Jurors(index as u32))
+            );
+        }
+        assert_eq!(
+            tampered(3, |d| d.rand = None),
+            Some(ReplayError::Malformed(3))
+        );
+    }
+
+    // The ids can not be recomputed without the secrets, but must be sorted and
+    // one for each juror added with a commitment.
+    #[test]
+    fn blinded() {
+        assert_eq!(
+            tampered(3, |d| d.blinded.as_mut().unwrap().reverse()),
+            Some(ReplayError::Blinded(3))
+        );
+        assert_eq!(
+            tampered(3, |d| d.blinded.as_mut().unwrap().push(vec![0xff; 32])),
+            Some(ReplayError::Blinded(3))
+        );
+        assert_eq!(
+            tampered(3, |d| d.blinded.as_mut().unwrap().truncate(1)),
+            Some(ReplayError::Blinded(3))
+        );
+    }
+
+    // Juror [b'j', i] was added with secret [i; 32] if i % 3 != 0.
+    #[test]
+    fn tokens() {
+        let blocks = blocks(CHAIN);
+        let data = &blocks[3].data;
+        let rand = data.rand.as_ref().unwrap();
+        let blinded = data.blinded.as_ref().unwrap();
+        // Drawn with 6a02, 6a15 and 6a01 then alternates 6a09 and 6a12.
+        assert_eq!(blinded.len(), 2);
+        assert!(find_token(blinded, &token(&[0x02; 32], rand)));
+        assert!(find_token(blinded, &token(&[0x01; 32], rand)));
+        assert!(!find_token(blinded, &token(&[0x15; 32], rand)));
+        assert!(!find_token(blinded, &token(&[0x13; 32], rand)));
+        assert!(!find_token(blinded, &token(&[0x02; 32], &[0; 32])));
+    }
+
+    #[test]
+    fn serving() {
+        assert_eq!(
+            tampered(14, |d| d.serving.as_mut().unwrap().truncate(1)),
+            Some(ReplayError::Serving(14))
+        );
+    }
+
+    #[test]
+    fn closed() {
+        let mut blocks = blocks(CHAIN);
+        // Close the jury of block 14 before its Expand.
+        let mut close = blocks[17].clone();
+        close.data.jurors_index = Some(14);
+        blocks.insert(15, close);
+        assert_eq!(replay(&blocks).err(), Some(ReplayError::Closed(16)));
+        assert_eq!(
+            tampered(17, |d| d.jurors_index = None),
+            Some(ReplayError::Malformed(17))
+        );
+    }
+
+    // The replay agrees with get_panel of the canister which recorded the chain.
+    #[test]
+    fn panels() {
+        let replayed = replay(&blocks(CHAIN)).unwrap();
+        let panels = [
+            (3, vec!["6a13", "6a01", "6a11", "6a16", "6a09"]),
+            (4, vec!["6a13", "6a0f"]),
+            (5, vec!["6a0e", "6a00", "6a07"]),
+            (6, vec!["6300", "6303"]),
+            (14, vec!["6a02", "6a0f", "6a03", "6a15", "6a04", "6a17"]),
+        ];
+        for (index, panel) in panels {
+            assert_eq!(replayed.panel(index), Some(jurors(&panel)));
+        }
+        // The Expand and Replace blocks of the jury of block 3.
+        for index in [7, 8] {
+            assert_eq!(replayed.panel(index), replayed.panel(3));
+        }
+    }
+
+    #[test]
+    fn replaced() {
+        assert_eq!(
+            tampered(8, |d| other(&mut d.jurors)),
+            Some(ReplayError::Jurors(8))
+        );
+    }
+
+    #[test]
+    fn strikes() {
+        assert_eq!(
+            tampered(9, |d| other(&mut d.jurors)),
+            Some(ReplayError::Panel(9))
+        );
+        let mut blocks = blocks(CHAIN);
+        // A second strike by the party, which has one.
+        let mut strike = blocks[9].clone();
+        strike.data.jurors = vec![vec![b'j', 0x11]];
+        blocks.insert(10, strike);
+        assert_eq!(replay(&blocks).err(), Some(ReplayError::Strikes(10)));
+    }
+
+    #[test]
+    fn alternates() {
+        assert_eq!(
+            tampered(3, |d| other(d.alternates.as_mut().unwrap())),
+            Some(ReplayError::Jurors(3))
+        );
+        assert_eq!(
+            tampered(10, |d| d.jurors = vec![vec![b'j', 0x13]]),
+            Some(ReplayError::Alternate(10))
+        );
+    }
+}